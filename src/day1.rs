@@ -1,12 +1,13 @@
+use crate::ParseError;
 use aoc_runner_derive::{aoc, aoc_generator};
 
 #[aoc_generator(day1)]
-fn generate<'a>(input: &str) -> Vec<String> {
-    input.lines().map(|s| s.into()).collect()
+fn generate(input: &str) -> Result<Vec<String>, ParseError> {
+    Ok(input.lines().map(|s| s.into()).collect())
 }
 
 #[aoc(day1, part1)]
-fn solve_part1(input: &Vec<String>) -> u32 {
+fn solve_part1(input: &[String]) -> u32 {
     let mut total = 0u32;
     for line in input {
         let mut val = 0u32;
@@ -55,7 +56,7 @@ fn parse_leading<S: AsRef<str>>(s: S) -> Option<u32> {
 }
 
 #[aoc(day1, part2)]
-fn solve_part2(input: &Vec<String>) -> u32 {
+fn solve_part2(input: &[String]) -> u32 {
     let mut total = 0u32;
     for line in input {
         let mut val = 0u32;
@@ -1,4 +1,4 @@
-use crate::Dir;
+use crate::{Dir, Grid, ParseError, Plane};
 use std::fmt::Display;
 
 use aoc_runner_derive::{aoc, aoc_generator};
@@ -105,62 +105,25 @@ impl Tile {
 }
 
 struct Map {
-    tiles: Vec<Vec<Tile>>,
-    width: usize,
-    height: usize,
-    start: (usize, usize),
+    tiles: Grid<Tile>,
+    start: (i64, i64),
 }
 
 impl Map {
-    /// Returns true iff the tile in the given directon from `(x, y)` has an exit leading to `(x,
-    /// y)`.
-    fn is_connected(&self, (x, y): (usize, usize), dir: Dir) -> bool {
-        self.neighbor((x, y), dir)
-            .is_some_and(|(xp, yp)| self.tiles[yp][xp].exits_toward(dir.inverse()))
-    }
-    /// Returns the coordinates of the tile in the given direction from the tile at `(x, y)`, if it
-    /// exists.
-    fn neighbor(&self, (x, y): (usize, usize), dir: Dir) -> Option<(usize, usize)> {
-        match dir {
-            Dir::N => {
-                if y == 0 {
-                    None
-                } else {
-                    Some((x, y - 1))
-                }
-            }
-            Dir::S => {
-                if y >= self.tiles.len() {
-                    None
-                } else {
-                    Some((x, y + 1))
-                }
-            }
-            Dir::W => {
-                if x == 0 {
-                    None
-                } else {
-                    Some((x - 1, y))
-                }
-            }
-            Dir::E => {
-                if x >= self.tiles[y].len() {
-                    None
-                } else {
-                    Some((x + 1, y))
-                }
-            }
-        }
+    /// Returns true iff the tile in the given directon from `pos` has an exit leading to `pos`.
+    fn is_connected(&self, pos: (i64, i64), dir: Dir) -> bool {
+        dir.neighbor(pos, &self.tiles)
+            .is_some_and(|p| self.tiles[p].exits_toward(dir.inverse()))
     }
 
     /// Follow the maze.
     fn travel(&self, v: MazeVector) -> Result<MazeVector, String> {
-        let tile = self.tiles[v.pos.1][v.pos.0];
+        let tile = self.tiles[v.pos];
         let exit_dir = tile
             .thru(v.dir)
             .ok_or::<String>("Invalid direction of travel".into())?;
-        let exit_pos = self
-            .neighbor(v.pos, exit_dir)
+        let exit_pos = exit_dir
+            .neighbor(v.pos, &self.tiles)
             .ok_or::<String>("Traveled off the map!".into())?;
         Ok(MazeVector {
             dir: exit_dir.inverse(),
@@ -171,13 +134,7 @@ impl Map {
 
 impl Display for Map {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for line in &self.tiles {
-            for tile in line {
-                write!(f, "{}", tile)?;
-            }
-            writeln!(f)?;
-        }
-        Ok(())
+        write!(f, "{}", self.tiles)
     }
 }
 
@@ -186,41 +143,24 @@ struct MazeVector {
     /// Incoming direction of travel.
     dir: Dir,
     /// Current location.
-    pos: (usize, usize),
+    pos: (i64, i64),
 }
 
 #[aoc_generator(day10)]
-fn generate(input: &str) -> Map {
-    let mut start = None;
-    let tiles = input
-        .lines()
-        .enumerate()
-        .map(|(y, line)| {
-            line.chars()
-                .enumerate()
-                .map(|(x, c)| match c {
-                    'S' => {
-                        start = Some((x, y));
-                        Tile::Start
-                    }
-                    '|' => Tile::NS,
-                    '-' => Tile::EW,
-                    'L' => Tile::NE,
-                    'J' => Tile::NW,
-                    '7' => Tile::SW,
-                    'F' => Tile::SE,
-                    '.' => Tile::Empty,
-                    _ => panic!("invalid char {:?}", c),
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
-    Map {
-        height: tiles.len(),
-        width: tiles[0].len(),
-        tiles,
-        start: start.unwrap(),
-    }
+fn generate(input: &str) -> Result<Map, ParseError> {
+    let tiles = Grid::parse(10, input, |c| match c {
+        'S' => Some(Tile::Start),
+        '|' => Some(Tile::NS),
+        '-' => Some(Tile::EW),
+        'L' => Some(Tile::NE),
+        'J' => Some(Tile::NW),
+        '7' => Some(Tile::SW),
+        'F' => Some(Tile::SE),
+        '.' => Some(Tile::Empty),
+        _ => None,
+    })?;
+    let start = tiles.find_unique(10, "start tile", |tile| *tile == Tile::Start)?;
+    Ok(Map { tiles, start })
 }
 
 #[aoc(day10, part1)]
@@ -236,12 +176,12 @@ fn solve_part1(input: &Map) -> i64 {
     // Then, walk both directions until they meet or cross over.
     let mut left = MazeVector {
         dir: start_dirs[0].inverse(),
-        pos: input.neighbor(input.start, start_dirs[0]).unwrap(),
+        pos: start_dirs[0].neighbor(input.start, &input.tiles).unwrap(),
     };
 
     let mut right = MazeVector {
         dir: start_dirs[1].inverse(),
-        pos: input.neighbor(input.start, start_dirs[1]).unwrap(),
+        pos: start_dirs[1].neighbor(input.start, &input.tiles).unwrap(),
     };
 
     let mut len = 1;
@@ -275,12 +215,12 @@ fn solve_part2(input: &Map) -> i64 {
     // First, we walk the full loop from the start point, storing each tile we visit in a fresh grid.
     let mut current = MazeVector {
         dir: start_dirs[0].inverse(),
-        pos: input.neighbor(input.start, start_dirs[0]).unwrap(),
+        pos: start_dirs[0].neighbor(input.start, &input.tiles).unwrap(),
     };
 
-    let mut tiles = vec![vec![Tile::Empty; input.width]; input.height];
+    let mut tiles = Grid::filled_with(Tile::Empty, input.tiles.width(), input.tiles.height());
     loop {
-        tiles[current.pos.1][current.pos.0] = input.tiles[current.pos.1][current.pos.0];
+        tiles[current.pos] = input.tiles[current.pos];
         current = input.travel(current).unwrap();
         if current.pos == input.start {
             break;
@@ -288,30 +228,30 @@ fn solve_part2(input: &Map) -> i64 {
     }
 
     // Fill in the start tile with what it actually is.
-    tiles[input.start.1][input.start.0] = (start_dirs[0], start_dirs[1]).into();
+    tiles[input.start] = (start_dirs[0], start_dirs[1]).into();
 
     // Now, for each row of the grid, we use the even-odd rule to determine which x values are
     // enclosed by the path.
     let mut area = 0;
-    for y in 0..input.height {
+    for row in tiles.rows() {
         let mut inside = false;
         let mut last_corner = Tile::Empty;
-        for x in 0..input.width {
-            if tiles[y][x] == Tile::NS {
+        for &tile in row {
+            if tile == Tile::NS {
                 inside = !inside;
-            } else if tiles[y][x].is_corner() && last_corner == Tile::Empty {
+            } else if tile.is_corner() && last_corner == Tile::Empty {
                 inside = !inside;
-                last_corner = tiles[y][x];
-            } else if tiles[y][x].is_corner() {
+                last_corner = tile;
+            } else if tile.is_corner() {
                 // The intuition here is that when we're on a horizontal edge, the next corner will
                 // tell us if we have changed sides. If the path leaves the horizontal in the opposite
                 // direction that it entered, the second corner doesn't count as a crossing since we
                 // already counted it coming in (in the previous branch).
-                if !last_corner.is_opposite_corner(tiles[y][x]) {
+                if !last_corner.is_opposite_corner(tile) {
                     inside = !inside;
                 }
                 last_corner = Tile::Empty;
-            } else if tiles[y][x] == Tile::Empty && inside {
+            } else if tile == Tile::Empty && inside {
                 area += 1;
             }
         }
@@ -1,5 +1,6 @@
 use std::{collections::BTreeSet, fmt::Display};
 
+use crate::{Grid, ParseError, Plane};
 use aoc_runner_derive::{aoc, aoc_generator};
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
@@ -8,11 +9,18 @@ enum Tile {
     Galaxy,
 }
 
+impl Display for Tile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tile::Empty => write!(f, "."),
+            Tile::Galaxy => write!(f, "#"),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct Input {
-    map: Vec<Vec<Tile>>,
-    width: usize,
-    height: usize,
+    map: Grid<Tile>,
     empty_rows: BTreeSet<usize>,
     empty_columns: BTreeSet<usize>,
     galaxies: Vec<(usize, usize)>,
@@ -20,62 +28,37 @@ struct Input {
 
 impl Display for Input {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "size = ({},{})", self.width, self.height)?;
-        for y in 0..self.height {
-            for x in 0..self.width {
-                match self.map[y][x] {
-                    Tile::Empty => write!(f, ".")?,
-                    Tile::Galaxy => write!(f, "#")?,
-                }
-            }
-            writeln!(f)?;
-        }
-        Ok(())
+        writeln!(f, "size = ({},{})", self.map.width(), self.map.height())?;
+        write!(f, "{}", self.map)
     }
 }
 
 #[aoc_generator(day11)]
-fn generate(input: &str) -> Input {
-    let mut galaxies = vec![];
-    let map = input
-        .lines()
-        .enumerate()
-        .map(|(y, line)| {
-            line.chars()
-                .enumerate()
-                .map(|(x, c)| match c {
-                    '.' => Tile::Empty,
-                    '#' => {
-                        galaxies.push((x, y));
-                        Tile::Galaxy
-                    }
-                    _ => panic!("invalid char {:?}", c),
-                })
-                .collect::<Vec<_>>()
-        })
+fn generate(input: &str) -> Result<Input, ParseError> {
+    let map = Grid::parse(11, input, |c| match c {
+        '.' => Some(Tile::Empty),
+        '#' => Some(Tile::Galaxy),
+        _ => None,
+    })?;
+    let galaxies = map
+        .iter()
+        .filter(|(_, tile)| **tile == Tile::Galaxy)
+        .map(|((y, x), _)| (x as usize, y as usize))
         .collect::<Vec<_>>();
 
-    let width = map[0].len();
-    let height = map.len();
-    let mut empty_columns = (0..width).collect::<BTreeSet<_>>();
-    let mut empty_rows = (0..height).collect::<BTreeSet<_>>();
-    for y in 0..height {
-        for x in 0..width {
-            if map[y][x] == Tile::Galaxy {
-                empty_columns.remove(&x);
-                empty_rows.remove(&y);
-            }
-        }
+    let mut empty_columns = (0..map.width()).collect::<BTreeSet<_>>();
+    let mut empty_rows = (0..map.height()).collect::<BTreeSet<_>>();
+    for &(x, y) in &galaxies {
+        empty_columns.remove(&x);
+        empty_rows.remove(&y);
     }
 
-    Input {
-        width,
-        height,
+    Ok(Input {
         map,
         empty_rows,
         empty_columns,
         galaxies,
-    }
+    })
 }
 
 #[aoc(day11, part1)]
@@ -1,3 +1,4 @@
+use crate::{lines, ParseError};
 use aoc_runner_derive::{aoc, aoc_generator};
 use std::collections::HashMap;
 
@@ -39,25 +40,26 @@ impl Row {
 }
 
 #[aoc_generator(day12)]
-fn generate(input: &str) -> Vec<Row> {
-    input
-        .lines()
+fn generate(input: &str) -> Result<Vec<Row>, ParseError> {
+    lines(12, input)
         .map(|line| {
-            let mut s = line.split(" ");
-            let conditions = s.next().unwrap();
-            let segments = s.next().unwrap();
-            Row {
+            let (conditions, segments) = line.split_once(" ")?;
+            Ok(Row {
                 conditions: conditions
                     .chars()
-                    .map(|c| match c {
-                        '.' => Condition::Ok,
-                        '#' => Condition::Damaged,
-                        '?' => Condition::Unknown,
-                        _ => panic!("invalid char {:?}", c),
+                    .enumerate()
+                    .map(|(x, c)| match c {
+                        '.' => Ok(Condition::Ok),
+                        '#' => Ok(Condition::Damaged),
+                        '?' => Ok(Condition::Unknown),
+                        _ => Err(line.invalid_char(x, c)),
                     })
-                    .collect(),
-                segments: segments.split(",").map(|s| s.parse().unwrap()).collect(),
-            }
+                    .collect::<Result<_, _>>()?,
+                segments: segments
+                    .split(',')
+                    .map(|s| line.parse(s))
+                    .collect::<Result<_, _>>()?,
+            })
         })
         .collect()
 }
@@ -171,7 +173,7 @@ impl<'a> Remainder<'a> {
 }
 
 #[aoc(day12, part1)]
-fn solve_part1(input: &Vec<Row>) -> i64 {
+fn solve_part1(input: &[Row]) -> i64 {
     let mut map = HashMap::new();
     input
         .iter()
@@ -183,15 +185,14 @@ fn solve_part1(input: &Vec<Row>) -> i64 {
 }
 
 #[aoc(day12, part2)]
-fn solve_part2(input: &Vec<Row>) -> i64 {
+fn solve_part2(input: &[Row]) -> i64 {
     let unfolded = input.iter().map(|row| row.unfold()).collect::<Vec<_>>();
     let mut map = HashMap::new();
-    let n = unfolded
+    unfolded
         .iter()
         .map(|row| {
             let r: Remainder = row.into();
             r.count(&mut map)
         })
-        .sum();
-    n
+        .sum()
 }
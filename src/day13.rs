@@ -1,5 +1,6 @@
 use std::fmt::Debug;
 
+use crate::{lines, Grid, ParseError, Plane};
 use aoc_runner_derive::{aoc, aoc_generator};
 
 #[derive(PartialEq, Eq, Copy, Clone, Hash)]
@@ -19,56 +20,41 @@ impl Debug for Tile {
 
 #[derive(Clone, Debug)]
 struct Map {
-    rows: Vec<Vec<Tile>>,
-    width: usize,
-    height: usize,
+    tiles: Grid<Tile>,
 }
 
 #[aoc_generator(day13)]
-fn generate(input: &str) -> Vec<Map> {
-    input
-        .lines()
+fn generate(input: &str) -> Result<Vec<Map>, ParseError> {
+    lines(13, input)
         .collect::<Vec<_>>()
-        .split(|s| s.trim().is_empty())
+        .split(|line| line.text.trim().is_empty())
+        .filter(|v| !v.is_empty())
         .map(|v| {
-            let rows = v
-                .iter()
-                .map(|s| {
-                    s.chars()
-                        .map(|c| match c {
-                            '.' => Tile::Ash,
-                            '#' => Tile::Rock,
-                            _ => panic!("invalid char {:?}", c),
-                        })
-                        .collect::<Vec<_>>()
-                })
-                .collect::<Vec<_>>();
-            Map {
-                width: rows[0].len(),
-                height: rows.len(),
-                rows,
-            }
+            let tiles = Grid::parse_lines(13, v.iter().copied(), |c| match c {
+                '.' => Some(Tile::Ash),
+                '#' => Some(Tile::Rock),
+                _ => None,
+            })?;
+            Ok(Map { tiles })
         })
         .collect()
 }
 
 #[aoc(day13, part1)]
-fn solve_part1(input: &Vec<Map>) -> i64 {
+fn solve_part1(input: &[Map]) -> i64 {
     input
         .iter()
         .enumerate()
         .map(|(n, m)| {
-            let mut columnwise = vec![vec![]; m.width];
-            for x in 0..m.width {
-                for row in &m.rows {
-                    columnwise[x].push(row[x]);
-                }
-            }
-            for i in 1..m.height {
-                if m.rows[0..i]
+            let rows = m.tiles.rows().collect::<Vec<_>>();
+            let columnwise = (0..m.tiles.width() as i64)
+                .map(|x| m.tiles.column(x).copied().collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            for i in 1..rows.len() {
+                if rows[0..i]
                     .iter()
                     .rev()
-                    .zip(m.rows[i..].iter())
+                    .zip(rows[i..].iter())
                     .filter(|(a, b)| a != b)
                     .count()
                     == 0
@@ -76,7 +62,7 @@ fn solve_part1(input: &Vec<Map>) -> i64 {
                     return i as i64 * 100;
                 }
             }
-            for i in 1..m.width {
+            for i in 1..columnwise.len() {
                 if columnwise[0..i]
                     .iter()
                     .rev()
@@ -94,22 +80,20 @@ fn solve_part1(input: &Vec<Map>) -> i64 {
 }
 
 #[aoc(day13, part2)]
-fn solve_part2(input: &Vec<Map>) -> i64 {
+fn solve_part2(input: &[Map]) -> i64 {
     input
         .iter()
         .enumerate()
         .map(|(n, m)| {
-            let mut columnwise = vec![vec![]; m.width];
-            for x in 0..m.width {
-                for row in &m.rows {
-                    columnwise[x].push(row[x]);
-                }
-            }
-            for i in 1..m.height {
-                if m.rows[0..i]
+            let rows = m.tiles.rows().collect::<Vec<_>>();
+            let columnwise = (0..m.tiles.width() as i64)
+                .map(|x| m.tiles.column(x).copied().collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            for i in 1..rows.len() {
+                if rows[0..i]
                     .iter()
                     .rev()
-                    .zip(m.rows[i..].iter())
+                    .zip(rows[i..].iter())
                     .map(|(a, b)| a.iter().zip(b.iter()).filter(|(i, j)| i != j).count())
                     .sum::<usize>()
                     == 1
@@ -117,7 +101,7 @@ fn solve_part2(input: &Vec<Map>) -> i64 {
                     return i as i64 * 100;
                 }
             }
-            for i in 1..m.width {
+            for i in 1..columnwise.len() {
                 if columnwise[0..i]
                     .iter()
                     .rev()
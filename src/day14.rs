@@ -1,5 +1,5 @@
-use crate::Dir;
-use std::{collections::HashMap, fmt::Display};
+use crate::{find_cycle, state_after, Dir, Grid, ParseError, Plane, StateCycle};
+use std::fmt::Display;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
@@ -20,188 +20,571 @@ impl Display for Rock {
     }
 }
 
+/// Returns the bits of a word from `lo` up to, but not including, `hi`, which are at most 64.
+fn mask(lo: usize, hi: usize) -> u64 {
+    // Working in u128 means neither shift can overflow, so there's no need to branch.
+    let below = |n: usize| ((1u128 << n) - 1) as u64;
+    below(hi) & !below(lo)
+}
+
+/// Calls `f` with the index of each word overlapping bits `start..end` and the mask of those bits
+/// within it.
+fn for_each_word(start: usize, end: usize, mut f: impl FnMut(usize, u64)) {
+    let mut i = start;
+    while i < end {
+        let word = i / 64;
+        let hi = (end - word * 64).min(64);
+        f(word, mask(i % 64, hi));
+        i = word * 64 + hi;
+    }
+}
+
+/// The number of bits set in `words` from `start` up to, but not including, `end`.
+fn count_bits(words: &[u64], start: usize, end: usize) -> usize {
+    let mut count = 0;
+    for_each_word(start, end, |w, m| {
+        count += (words[w] & m).count_ones() as usize
+    });
+    count
+}
+
+/// Sets the bits in `words` from `start` up to, but not including, `end` to `on`.
+fn fill_bits(words: &mut [u64], start: usize, end: usize, on: bool) {
+    for_each_word(start, end, |w, m| {
+        if on {
+            words[w] |= m;
+        } else {
+            words[w] &= !m;
+        }
+    });
+}
+
+/// Transposes a 64x64 matrix of bits, where bit `j` of `rows[i]` is the entry in row `i` and
+/// column `j`. Each step swaps the off-diagonal blocks of every block on the diagonal, halving the
+/// block size each time.
+fn transpose64(rows: &mut [u64; 64]) {
+    let mut size = 32;
+    let mut mask: u64 = 0x0000_0000_ffff_ffff;
+    while size != 0 {
+        // Visit the rows in the top half of each block.
+        let mut k = 0;
+        while k < 64 {
+            let t = ((rows[k] >> size) ^ rows[k + size]) & mask;
+            rows[k] ^= t << size;
+            rows[k + size] ^= t;
+            k = (k + size + 1) & !size;
+        }
+        size >>= 1;
+        mask ^= mask << size;
+    }
+}
+
+/// One bit for each cell of a grid, stored a row at a time with each row starting on a new word.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct Map {
-    rocks: Vec<Vec<Rock>>,
+struct Bitboard {
     width: usize,
     height: usize,
+    /// The number of words in each row.
+    stride: usize,
+    words: Vec<u64>,
 }
 
-impl Display for Map {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for row in &self.rocks {
-            for c in row {
-                write!(f, "{c}")?;
-            }
-            writeln!(f)?;
+impl Bitboard {
+    fn new(width: usize, height: usize) -> Self {
+        let stride = width.div_ceil(64);
+        Bitboard {
+            width,
+            height,
+            stride,
+            words: vec![0; stride * height],
         }
-        Ok(())
     }
-}
 
-impl Map {
-    fn tilt(&mut self, dir: Dir) {
-        match dir {
-            Dir::N => {
-                for x in 0..self.width {
-                    let mut current = 0;
-                    for y in (0..self.height).rev() {
-                        match self.rocks[y][x] {
-                            Rock::Round => {
-                                current += 1;
-                                self.rocks[y][x] = Rock::None;
-                            }
-                            Rock::Square => {
-                                for i in 0..current {
-                                    self.rocks[y + i + 1][x] = Rock::Round;
-                                }
-                                current = 0;
-                            }
-                            Rock::None => {}
-                        }
-                    }
-                    for i in 0..current {
-                        self.rocks[i][x] = Rock::Round;
+    fn row(&self, y: usize) -> &[u64] {
+        &self.words[y * self.stride..(y + 1) * self.stride]
+    }
+
+    fn row_mut(&mut self, y: usize) -> &mut [u64] {
+        &mut self.words[y * self.stride..(y + 1) * self.stride]
+    }
+
+    fn get(&self, (y, x): (usize, usize)) -> bool {
+        self.row(y)[x / 64] >> (x % 64) & 1 == 1
+    }
+
+    fn set(&mut self, (y, x): (usize, usize)) {
+        self.row_mut(y)[x / 64] |= 1 << (x % 64);
+    }
+
+    /// Iterates over the positions of the set bits, a row at a time.
+    fn ones(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..self.height).flat_map(move |y| {
+            self.row(y).iter().enumerate().flat_map(move |(w, word)| {
+                let mut word = *word;
+                std::iter::from_fn(move || {
+                    if word == 0 {
+                        return None;
                     }
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    Some((y, w * 64 + bit))
+                })
+            })
+        })
+    }
+
+    /// Returns the same bits, a column at a time. This works on 64x64 blocks at a time.
+    fn transpose(&self) -> Bitboard {
+        let mut result = Bitboard::new(self.height, self.width);
+        let mut block = [0; 64];
+        for by in 0..self.height.div_ceil(64) {
+            for bx in 0..self.stride {
+                for (i, word) in block.iter_mut().enumerate() {
+                    let y = by * 64 + i;
+                    *word = if y < self.height { self.row(y)[bx] } else { 0 };
                 }
-            }
-            Dir::S => {
-                for x in 0..self.width {
-                    let mut current = 0;
-                    for y in 0..self.height {
-                        match self.rocks[y][x] {
-                            Rock::Round => {
-                                current += 1;
-                                self.rocks[y][x] = Rock::None;
-                            }
-                            Rock::Square => {
-                                for i in 0..current {
-                                    self.rocks[y - (i + 1)][x] = Rock::Round;
-                                }
-                                current = 0;
-                            }
-                            Rock::None => {}
-                        }
-                    }
-                    for i in 0..current {
-                        self.rocks[self.height - 1 - i][x] = Rock::Round;
+                transpose64(&mut block);
+                for (i, word) in block.iter().enumerate() {
+                    let x = bx * 64 + i;
+                    if x < self.width {
+                        result.row_mut(x)[by] = *word;
                     }
                 }
             }
-            Dir::W => {
-                for y in 0..self.height {
-                    let mut current = 0;
-                    for x in (0..self.width).rev() {
-                        match self.rocks[y][x] {
-                            Rock::Round => {
-                                current += 1;
-                                self.rocks[y][x] = Rock::None;
-                            }
-                            Rock::Square => {
-                                for i in 0..current {
-                                    self.rocks[y][x + i + 1] = Rock::Round;
-                                }
-                                current = 0;
+        }
+        result
+    }
+}
+
+/// The parts of the platform that don't move.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Layout {
+    square: Bitboard,
+    /// For each row, the runs of cells between square rocks, as `(start, end)` pairs.
+    row_gaps: Vec<Vec<(usize, usize)>>,
+    /// The same for each column.
+    column_gaps: Vec<Vec<(usize, usize)>>,
+}
+
+impl Layout {
+    fn new(square: Bitboard) -> Self {
+        let gaps = |board: &Bitboard| {
+            (0..board.height)
+                .map(|y| {
+                    let mut gaps = vec![];
+                    let mut start = 0;
+                    for x in 0..=board.width {
+                        if x == board.width || board.get((y, x)) {
+                            if start < x {
+                                gaps.push((start, x));
                             }
-                            Rock::None => {}
+                            start = x + 1;
                         }
                     }
-                    for i in 0..current {
-                        self.rocks[y][i] = Rock::Round;
+                    gaps
+                })
+                .collect()
+        };
+        Layout {
+            row_gaps: gaps(&square),
+            column_gaps: gaps(&square.transpose()),
+            square,
+        }
+    }
+
+    /// Slides the round rocks in each row of `round` as far as they go toward the start of the
+    /// row, or the end if `to_end` is set, given the runs of cells between square rocks.
+    fn slide(round: &mut Bitboard, gaps: &[Vec<(usize, usize)>], to_end: bool) {
+        for (y, gaps) in gaps.iter().enumerate() {
+            let row = round.row_mut(y);
+            for &(start, end) in gaps {
+                let word = start / 64;
+                if word == (end - 1) / 64 {
+                    // The usual case, where the whole run is in one word.
+                    let (lo, hi) = (start % 64, end - word * 64);
+                    let n = (row[word] & mask(lo, hi)).count_ones() as usize;
+                    let rocks = if to_end {
+                        mask(hi - n, hi)
+                    } else {
+                        mask(lo, lo + n)
+                    };
+                    row[word] = row[word] & !mask(lo, hi) | rocks;
+                } else {
+                    let n = count_bits(row, start, end);
+                    fill_bits(row, start, end, false);
+                    if to_end {
+                        fill_bits(row, end - n, end, true);
+                    } else {
+                        fill_bits(row, start, start + n, true);
                     }
                 }
             }
-            Dir::E => {
-                for y in 0..self.height {
-                    let mut current = 0;
-                    for x in 0..self.width {
-                        match self.rocks[y][x] {
-                            Rock::Round => {
-                                current += 1;
-                                self.rocks[y][x] = Rock::None;
-                            }
-                            Rock::Square => {
-                                for i in 0..current {
-                                    self.rocks[y][x - (i + 1)] = Rock::Round;
-                                }
-                                current = 0;
-                            }
-                            Rock::None => {}
-                        }
-                    }
-                    for i in 0..current {
-                        self.rocks[y][self.width - 1 - i] = Rock::Round;
-                    }
-                }
+        }
+    }
+
+    /// Tilts the platform in each of `sequence`'s directions in turn, returning where the round
+    /// rocks end up.
+    fn spin(&self, round: &Bitboard, sequence: &[Dir]) -> Bitboard {
+        let mut round = round.clone();
+        for dir in sequence {
+            self.tilt(&mut round, *dir);
+        }
+        round
+    }
+
+    fn tilt(&self, round: &mut Bitboard, dir: Dir) {
+        match dir {
+            Dir::W | Dir::E => Self::slide(round, &self.row_gaps, dir == Dir::E),
+            Dir::N | Dir::S => {
+                let mut columns = round.transpose();
+                Self::slide(&mut columns, &self.column_gaps, dir == Dir::S);
+                *round = columns.transpose();
             }
         }
     }
+}
 
-    fn load(&self) -> i64 {
-        self.rocks
-            .iter()
-            .rev()
-            .enumerate()
-            .map(|(i, row)| (row.iter().filter(|r| **r == Rock::Round).count() * (i + 1)) as i64)
-            .sum()
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Map {
+    layout: Layout,
+    round: Bitboard,
+}
+
+impl Display for Map {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut rocks = Grid::filled_with(Rock::None, self.round.width, self.round.height);
+        for (board, rock) in [
+            (&self.round, Rock::Round),
+            (&self.layout.square, Rock::Square),
+        ] {
+            for (y, x) in board.ones() {
+                rocks[(y as i64, x as i64)] = rock;
+            }
+        }
+        write!(f, "{rocks}")
+    }
+}
+
+impl Map {
+    fn tilt(&mut self, dir: Dir) {
+        self.layout.tilt(&mut self.round, dir);
+    }
+
+    /// Tilts the platform in each of `sequence`'s directions in turn, `count` times over.
+    fn spin(&self, sequence: &[Dir], count: u64) -> Map {
+        // Only the round rocks move, so they are all we need to keep track of.
+        let round = state_after(self.round.clone(), count, |round| {
+            self.layout.spin(round, sequence)
+        });
+        Map {
+            layout: self.layout.clone(),
+            round,
+        }
+    }
+
+    fn load(&self, beam: Dir) -> i64 {
+        line_loads(&self.round, beam).iter().sum()
+    }
+
+    /// Spins the platform until it gets back into a position it has been in before, and returns
+    /// the load on `beam` along the way.
+    fn load_series(&self, sequence: &[Dir], beam: Dir) -> LoadSeries {
+        let cycle = find_cycle(self.round.clone(), |round| {
+            self.layout.spin(round, sequence)
+        });
+        LoadSeries {
+            cycle: StateCycle {
+                history: cycle
+                    .history
+                    .iter()
+                    .map(|round| line_loads(round, beam).iter().sum())
+                    .collect(),
+                offset: cycle.offset,
+                period: cycle.period,
+            },
+        }
+    }
+}
+
+/// Returns the load that the round rocks put on the support beam on the `beam` side of the
+/// platform, from each column for the north and south beams, or each row for the east and west
+/// beams. Each rock adds the number of cells from the far edge up to and including its own.
+fn line_loads(round: &Bitboard, beam: Dir) -> Vec<i64> {
+    let (height, width) = (round.height, round.width);
+    let lines = match beam {
+        Dir::N | Dir::S => width,
+        Dir::E | Dir::W => height,
+    };
+    let mut loads = vec![0; lines];
+    for (y, x) in round.ones() {
+        let (line, load) = match beam {
+            Dir::N => (x, height - y),
+            Dir::S => (x, y + 1),
+            Dir::W => (y, width - x),
+            Dir::E => (y, x + 1),
+        };
+        loads[line] += load as i64;
+    }
+    loads
+}
+
+/// The loads on a platform's support beams.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Loads {
+    pub north: i64,
+    pub south: i64,
+    pub east: i64,
+    pub west: i64,
+    /// The load on the north beam from each column, from west to east.
+    pub columns: Vec<i64>,
+}
+
+/// Parses a platform and returns the loads on its support beams, without tilting it.
+pub fn loads(input: &str) -> Result<Loads, ParseError> {
+    let map = generate(input)?;
+    let columns = line_loads(&map.round, Dir::N);
+    Ok(Loads {
+        north: columns.iter().sum(),
+        south: map.load(Dir::S),
+        east: map.load(Dir::E),
+        west: map.load(Dir::W),
+        columns,
+    })
+}
+
+/// How the load on a beam changes as a platform is spun over and over.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadSeries {
+    /// The load after each spin, which repeats along with the positions of the rocks.
+    cycle: StateCycle<i64>,
+}
+
+impl LoadSeries {
+    /// The load before any spins, then after each one, up to but not including the first time the
+    /// platform gets back into a position it has been in before.
+    pub fn loads(&self) -> &[i64] {
+        &self.cycle.history
     }
+
+    /// The number of spins before the loads start repeating.
+    pub fn offset(&self) -> usize {
+        self.cycle.offset
+    }
+
+    pub fn period(&self) -> usize {
+        self.cycle.period
+    }
+
+    /// The load after any number of spins.
+    pub fn after(&self, spins: u64) -> i64 {
+        self.cycle.history[self.cycle.index(spins)]
+    }
+}
+
+/// Parses a platform, spins it by tilting it in each of `sequence`'s directions in turn until it
+/// repeats itself, and returns the load on `beam` after each spin.
+pub fn load_series(input: &str, sequence: &[Dir], beam: Dir) -> Result<LoadSeries, ParseError> {
+    Ok(generate(input)?.load_series(sequence, beam))
 }
 
 #[aoc_generator(day14)]
-fn generate(input: &str) -> Map {
-    let rocks = input
-        .lines()
-        .map(|line| {
-            line.chars()
-                .map(|c| match c {
-                    'O' => Rock::Round,
-                    '#' => Rock::Square,
-                    '.' => Rock::None,
-                    _ => panic!("invalid char {c}"),
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
-    Map {
-        width: rocks[0].len(),
-        height: rocks.len(),
-        rocks,
+fn generate(input: &str) -> Result<Map, ParseError> {
+    let rocks = Grid::parse(14, input, |c| match c {
+        'O' => Some(Rock::Round),
+        '#' => Some(Rock::Square),
+        '.' => Some(Rock::None),
+        _ => None,
+    })?;
+    let mut round = Bitboard::new(rocks.width(), rocks.height());
+    let mut square = round.clone();
+    for ((y, x), rock) in rocks.iter() {
+        let pos = (y as usize, x as usize);
+        match rock {
+            Rock::Round => round.set(pos),
+            Rock::Square => square.set(pos),
+            Rock::None => {}
+        }
     }
+    Ok(Map {
+        layout: Layout::new(square),
+        round,
+    })
 }
 
 #[aoc(day14, part1)]
 fn solve_part1(input: &Map) -> i64 {
     let mut map = input.clone();
     map.tilt(Dir::N);
-    map.load()
+    map.load(Dir::N)
 }
 
+/// One spin cycle.
+const CYCLE: [Dir; 4] = [Dir::N, Dir::W, Dir::S, Dir::E];
+
 #[aoc(day14, part2)]
 fn solve_part2(input: &Map) -> i64 {
-    let mut map = input.clone();
-    let mut seen = HashMap::new();
-    seen.insert(map.clone(), 0);
-    let n = loop {
-        map.tilt(Dir::N);
-        map.tilt(Dir::W);
-        map.tilt(Dir::S);
-        map.tilt(Dir::E);
-
-        if let Some(n) = seen.get(&map) {
-            break n;
-        }
-        seen.insert(map.clone(), seen.len());
-    };
+    input.spin(&CYCLE, 1000000000).load(Dir::N)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+O....#....
+O.OO#....#
+.....##...
+OO.#O....O
+.O.....O#.
+O.#..O.#.#
+..O..#O..O
+.......O..
+#....###..
+#OO..#....
+";
+
+    /// Tilts a grid of characters by moving one rock one cell at a time until none can move.
+    fn naive_tilt(cells: &mut [Vec<u8>], dir: Dir) {
+        let (dy, dx) = dir.delta();
+        let (height, width) = (cells.len() as i64, cells[0].len() as i64);
+        let mut moved = true;
+        while moved {
+            moved = false;
+            for y in 0..height {
+                for x in 0..width {
+                    let (ny, nx) = (y + dy, x + dx);
+                    if cells[y as usize][x as usize] == b'O'
+                        && (0..height).contains(&ny)
+                        && (0..width).contains(&nx)
+                        && cells[ny as usize][nx as usize] == b'.'
+                    {
+                        cells[y as usize][x as usize] = b'.';
+                        cells[ny as usize][nx as usize] = b'O';
+                        moved = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn render(cells: &[Vec<u8>]) -> String {
+        cells
+            .iter()
+            .map(|row| String::from_utf8(row.clone()).unwrap() + "\n")
+            .collect()
+    }
+
+    fn cells(input: &str) -> Vec<Vec<u8>> {
+        input.lines().map(|line| line.bytes().collect()).collect()
+    }
+
+    /// Builds a random platform with the given dimensions, from a small xorshift generator.
+    fn random_platform(seed: u64, width: usize, height: usize) -> String {
+        let mut state = seed;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        (0..height)
+            .map(|_| {
+                let row: String = (0..width)
+                    .map(|_| match next() % 10 {
+                        0..=2 => 'O',
+                        3 => '#',
+                        _ => '.',
+                    })
+                    .collect();
+                row + "\n"
+            })
+            .collect()
+    }
+
+    #[test]
+    fn example() {
+        let map = generate(EXAMPLE).unwrap();
+        assert_eq!(solve_part1(&map), 136);
+        assert_eq!(solve_part2(&map), 64);
+    }
+
+    #[test]
+    fn tilt_matches_naive() {
+        let sizes = [
+            (1, 1),
+            (7, 5),
+            (64, 64),
+            (65, 3),
+            (3, 130),
+            (129, 70),
+            (150, 200),
+        ];
+        for (seed, (width, height)) in (1u64..).zip(sizes) {
+            let input = random_platform(seed.wrapping_mul(0x9e3779b97f4a7c15), width, height);
+            let map = generate(&input).unwrap();
+            for dir in [Dir::N, Dir::S, Dir::E, Dir::W] {
+                let mut tilted = map.clone();
+                tilted.tilt(dir);
+                let mut expected = cells(&input);
+                naive_tilt(&mut expected, dir);
+                assert_eq!(
+                    tilted.to_string(),
+                    render(&expected),
+                    "{width}x{height} {dir:?}"
+                );
+            }
+        }
+    }
 
-    let cycle_length = seen.len() - n;
-    let steps = (1000000000 - seen.len()) % cycle_length;
+    #[test]
+    fn example_loads() {
+        assert_eq!(
+            loads(EXAMPLE),
+            Ok(Loads {
+                north: 104,
+                south: 94,
+                east: 77,
+                west: 121,
+                columns: vec![31, 14, 14, 9, 7, 5, 4, 9, 0, 11],
+            })
+        );
+    }
 
-    for _ in 0..steps {
-        map.tilt(Dir::N);
-        map.tilt(Dir::W);
-        map.tilt(Dir::S);
-        map.tilt(Dir::E);
+    #[test]
+    fn load_series_matches_spins() {
+        let map = generate(EXAMPLE).unwrap();
+        let series = load_series(EXAMPLE, &CYCLE, Dir::N).unwrap();
+        assert_eq!((series.offset(), series.period()), (3, 7));
+        assert_eq!(series.loads().len(), 10);
+        for n in (0..40).chain([999_999_999, 1_000_000_000]) {
+            assert_eq!(
+                series.after(n),
+                map.spin(&CYCLE, n).load(Dir::N),
+                "{n} spins"
+            );
+        }
+        assert_eq!(series.after(1_000_000_000), 64);
     }
 
-    map.load()
+    #[test]
+    fn spin_in_any_order() {
+        let map = generate(EXAMPLE).unwrap();
+        for sequence in [
+            &[Dir::E, Dir::N, Dir::E, Dir::S, Dir::W][..],
+            &[Dir::S, Dir::E],
+            &[Dir::W],
+            &[],
+        ] {
+            let mut cells = cells(EXAMPLE);
+            for count in 0..=3000 {
+                if count % 100 == 0 || count < 20 {
+                    let spun = map.spin(sequence, count);
+                    assert_eq!(spun.to_string(), render(&cells), "{sequence:?} x {count}");
+                }
+                for dir in sequence {
+                    naive_tilt(&mut cells, *dir);
+                }
+            }
+        }
+    }
 }
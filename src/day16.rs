@@ -1,6 +1,6 @@
-use crate::Dir;
+use crate::{search, Dir, Grid, ParseError, Plane};
 use aoc_runner_derive::{aoc, aoc_generator};
-use array2d::Array2D;
+use std::fmt::Display;
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
 enum Mirror {
@@ -16,93 +16,175 @@ enum Mirror {
     Empty,
 }
 
+impl Mirror {
+    /// Returns the direction that a beam travelling in `dir` leaves this tile in, along with the
+    /// direction of a second beam if the tile splits it.
+    fn deflect(self, dir: Dir) -> (Dir, Option<Dir>) {
+        match self {
+            Mirror::Forward => match dir {
+                Dir::E | Dir::W => (dir.ccw(), None),
+                Dir::N | Dir::S => (dir.cw(), None),
+            },
+            Mirror::Backward => match dir {
+                Dir::E | Dir::W => (dir.cw(), None),
+                Dir::N | Dir::S => (dir.ccw(), None),
+            },
+            Mirror::SplitHorizontal if dir == Dir::N || dir == Dir::S => (Dir::E, Some(Dir::W)),
+            Mirror::SplitVertical if dir == Dir::E || dir == Dir::W => (Dir::N, Some(Dir::S)),
+            _ => (dir, None),
+        }
+    }
+}
+
+impl Display for Mirror {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mirror::Forward => write!(f, "/"),
+            Mirror::Backward => write!(f, "\\"),
+            Mirror::SplitVertical => write!(f, "|"),
+            Mirror::SplitHorizontal => write!(f, "-"),
+            Mirror::Empty => write!(f, "."),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct Input {
-    map: Array2D<Mirror>,
+    map: Grid<Mirror>,
 }
 
+/// A beam of light entering the tile at `origin` while travelling in `dir`.
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
-struct LightVector {
-    origin: (i64, i64),
-    dir: Dir,
+pub struct LightVector {
+    pub origin: (i64, i64),
+    pub dir: Dir,
 }
 
 #[aoc_generator(day16)]
-fn generate(input: &str) -> Input {
-    let width = input.lines().next().unwrap().len();
-    let height = input.lines().count();
-    let map = Array2D::from_iter_row_major(
-        input
-            .lines()
-            .map(|line| {
-                line.chars().map(|c| match c {
-                    '/' => Mirror::Forward,
-                    '\\' => Mirror::Backward,
-                    '|' => Mirror::SplitVertical,
-                    '-' => Mirror::SplitHorizontal,
-                    '.' => Mirror::Empty,
-                    _ => panic!("invalid char {c}"),
+fn generate(input: &str) -> Result<Input, ParseError> {
+    let map = Grid::parse(16, input, |c| match c {
+        '/' => Some(Mirror::Forward),
+        '\\' => Some(Mirror::Backward),
+        '|' => Some(Mirror::SplitVertical),
+        '-' => Some(Mirror::SplitHorizontal),
+        '.' => Some(Mirror::Empty),
+        _ => None,
+    })?;
+    Ok(Input { map })
+}
+
+/// What the beams did in one tile.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub struct Tile {
+    pub energized: bool,
+    /// Whether a beam entered the tile travelling north, south, east and west, respectively.
+    pub entered: [bool; 4],
+}
+
+/// Returns where `dir` goes in `Tile::entered`.
+fn slot(dir: Dir) -> usize {
+    match dir {
+        Dir::N => 0,
+        Dir::S => 1,
+        Dir::E => 2,
+        Dir::W => 3,
+    }
+}
+
+/// Everywhere a beam went.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Beams {
+    map: Grid<Mirror>,
+    pub tiles: Grid<Tile>,
+}
+
+impl Beams {
+    /// The number of energized tiles.
+    pub fn count(&self) -> i64 {
+        self.tiles.iter().filter(|(_, t)| t.energized).count() as i64
+    }
+
+    pub fn mask(&self) -> Grid<bool> {
+        self.tiles.map(|t| t.energized)
+    }
+
+    /// Draws the energized tiles as `#` and everything else as `.`.
+    pub fn render_energized(&self) -> String {
+        self.tiles
+            .map(|t| if t.energized { '#' } else { '.' })
+            .to_string()
+    }
+
+    /// Draws the grid with the beams on it. Empty tiles show an arrow if one beam went through
+    /// them, or how many beams went through them if more than one did.
+    pub fn render_paths(&self) -> String {
+        let mut out = String::new();
+        for (mirrors, tiles) in self.map.rows().zip(self.tiles.rows()) {
+            for (mirror, tile) in mirrors.iter().zip(tiles) {
+                let entered = tile.entered.iter().filter(|e| **e).count();
+                match (mirror, entered) {
+                    (Mirror::Empty, 1) => {
+                        let dir = tile.entered.iter().position(|e| *e).unwrap();
+                        out.push(['^', 'v', '>', '<'][dir]);
+                    }
+                    (Mirror::Empty, 2..) => out.push_str(&entered.to_string()),
+                    _ => out.push_str(&mirror.to_string()),
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Draws the grid as a binary PPM image, with each tile taking up `scale` by `scale` pixels.
+    /// Energized tiles are yellow, and mirrors and splitters are lighter than empty tiles.
+    pub fn to_ppm(&self, scale: usize) -> Vec<u8> {
+        let (width, height) = (self.map.width() * scale, self.map.height() * scale);
+        let mut out = format!("P6\n{width} {height}\n255\n").into_bytes();
+        for (mirrors, tiles) in self.map.rows().zip(self.tiles.rows()) {
+            let row = mirrors
+                .iter()
+                .zip(tiles)
+                .flat_map(|(mirror, tile)| {
+                    let color: [u8; 3] = match (*mirror == Mirror::Empty, tile.energized) {
+                        (true, false) => [16, 16, 32],
+                        (false, false) => [112, 112, 128],
+                        (true, true) => [255, 192, 0],
+                        (false, true) => [255, 255, 224],
+                    };
+                    std::iter::repeat_n(color, scale).flatten()
                 })
-            })
-            .flatten(),
-        height,
-        width,
-    )
-    .unwrap();
-    Input { map }
+                .collect::<Vec<_>>();
+            for _ in 0..scale {
+                out.extend(&row);
+            }
+        }
+        out
+    }
 }
 
-fn trace(input: &Input, start: LightVector) -> i64 {
-    let mut energized = Array2D::<(bool, (bool, bool, bool, bool))>::filled_with(
-        (false, (false, false, false, false)),
-        input.map.num_rows(),
-        input.map.num_columns(),
-    );
+fn trace(input: &Input, start: LightVector) -> Beams {
+    let mut tiles =
+        Grid::<Tile>::filled_with(Tile::default(), input.map.width(), input.map.height());
+    tiles[start.origin].entered[slot(start.dir)] = true;
     let mut current = vec![start];
     let mut splits = vec![];
     while !current.is_empty() {
         for v in current.iter_mut() {
-            energized[(v.origin.0 as usize, v.origin.1 as usize)].0 = true;
-            match input.map[(v.origin.0 as usize, v.origin.1 as usize)] {
-                Mirror::Forward => match v.dir {
-                    Dir::E | Dir::W => v.dir = v.dir.ccw(),
-                    Dir::N | Dir::S => v.dir = v.dir.cw(),
-                },
-                Mirror::Backward => match v.dir {
-                    Dir::E | Dir::W => v.dir = v.dir.cw(),
-                    Dir::N | Dir::S => v.dir = v.dir.ccw(),
-                },
-                Mirror::SplitHorizontal => {
-                    if v.dir == Dir::N || v.dir == Dir::S {
-                        v.dir = Dir::E;
-                        splits.push(LightVector {
-                            origin: v.origin,
-                            dir: Dir::W,
-                        });
-                    }
-                }
-                Mirror::SplitVertical => {
-                    if v.dir == Dir::E || v.dir == Dir::W {
-                        v.dir = Dir::N;
-                        splits.push(LightVector {
-                            origin: v.origin,
-                            dir: Dir::S,
-                        });
-                    }
-                }
-                Mirror::Empty => {}
+            tiles[v.origin].energized = true;
+            let (dir, split) = input.map[v.origin].deflect(v.dir);
+            v.dir = dir;
+            if let Some(dir) = split {
+                splits.push(LightVector {
+                    origin: v.origin,
+                    dir,
+                });
             }
         }
-        current.extend(splits.drain(..));
+        current.append(&mut splits);
         current.retain_mut(|v| {
             if let Some(next) = v.dir.neighbor(v.origin, &input.map) {
-                let unext = (next.0 as usize, next.1 as usize);
-                let visited = match v.dir {
-                    Dir::N => &mut energized[unext].1 .0,
-                    Dir::S => &mut energized[unext].1 .1,
-                    Dir::E => &mut energized[unext].1 .2,
-                    Dir::W => &mut energized[unext].1 .3,
-                };
+                let visited = &mut tiles[next].entered[slot(v.dir)];
                 if !*visited {
                     v.origin = next;
                     *visited = true;
@@ -116,10 +198,10 @@ fn trace(input: &Input, start: LightVector) -> i64 {
         });
     }
 
-    energized
-        .elements_row_major_iter()
-        .filter(|(b, _)| *b)
-        .count() as i64
+    Beams {
+        map: input.map.clone(),
+        tiles,
+    }
 }
 
 #[aoc(day16, part1)]
@@ -131,42 +213,346 @@ fn solve_part1(input: &Input) -> i64 {
             dir: Dir::E,
         },
     )
+    .count()
+}
+
+/// The tiles that a beam passes through before it gets split or leaves the grid.
+struct Segment {
+    /// The index of each tile in the grid, including the one the beam starts in and the splitter
+    /// that ends it, in the order the beam reaches them.
+    tiles: Vec<usize>,
+    /// The index of the splitter that splits the beam, if it gets split.
+    end: Option<usize>,
+}
+
+/// The grid compiled into a graph of the splitters that beams can be split by, where each splitter
+/// leads to the splitters that the two beams leaving it run into.
+///
+/// Every beam that reaches a splitter energizes the same tiles from then on, so we work those out
+/// once for each splitter. Splitters that lead back to each other energize exactly the same tiles,
+/// so they are handled together as one strongly connected component.
+struct SegmentGraph<'a> {
+    map: &'a Grid<Mirror>,
+    /// The index of each splitter, for the tiles that have one.
+    splitters: Grid<Option<usize>>,
+    /// The component that each splitter is in.
+    component: Vec<usize>,
+    /// For each component, a bit for every tile in the grid, set if a beam split by any splitter
+    /// in the component energizes it.
+    energized: Vec<Vec<u64>>,
+}
+
+impl<'a> SegmentGraph<'a> {
+    fn new(map: &'a Grid<Mirror>) -> Self {
+        let mut count = 0;
+        let splitters = map.map(|m| match m {
+            Mirror::SplitHorizontal | Mirror::SplitVertical => {
+                count += 1;
+                Some(count - 1)
+            }
+            _ => None,
+        });
+        let mut graph = SegmentGraph {
+            map,
+            splitters,
+            component: vec![],
+            energized: vec![],
+        };
+        let words = graph.words();
+        // The tiles energized by each splitter's own beams, and the splitters they run into.
+        let mut tiles = vec![vec![0u64; words]; count];
+        let mut next = vec![vec![]; count];
+        for pos in map.positions() {
+            let Some(i) = graph.splitters[pos] else {
+                continue;
+            };
+            let (dir, split) = match map[pos] {
+                Mirror::SplitHorizontal => (Dir::E, Dir::W),
+                _ => (Dir::N, Dir::S),
+            };
+            for dir in [dir, split] {
+                let segment = graph.walk(LightVector { origin: pos, dir });
+                set_bits(&mut tiles[i], &segment.tiles);
+                next[i].extend(segment.end);
+            }
+        }
+        let components = search::strongly_connected_components(count, |i| next[i].iter().copied());
+        graph.component = vec![0; count];
+        // Each component comes after the ones it leads to, so theirs are already done.
+        for (c, members) in components.iter().enumerate() {
+            let mut energized = vec![0u64; words];
+            for &i in members {
+                graph.component[i] = c;
+            }
+            for &i in members {
+                union(&mut energized, &tiles[i]);
+                for &j in &next[i] {
+                    if graph.component[j] != c {
+                        union(&mut energized, &graph.energized[graph.component[j]]);
+                    }
+                }
+            }
+            graph.energized.push(energized);
+        }
+        graph
+    }
+
+    /// The number of words needed for a bit for every tile.
+    fn words(&self) -> usize {
+        (self.map.width() * self.map.height()).div_ceil(64)
+    }
+
+    fn index(&self, (y, x): (i64, i64)) -> usize {
+        y as usize * self.map.width() + x as usize
+    }
+
+    /// Follows a beam until it gets split or leaves the grid.
+    ///
+    /// If the beam starts at a splitter, it can come back to it. In that case it either gets split
+    /// by it, or carries on the same way as one of the splitter's own beams, so we stop there.
+    fn walk(&self, start: LightVector) -> Segment {
+        let mut v = start;
+        let mut tiles = vec![];
+        loop {
+            tiles.push(self.index(v.origin));
+            let (dir, split) = self.map[v.origin].deflect(v.dir);
+            if split.is_some() {
+                return Segment {
+                    tiles,
+                    end: self.splitters[v.origin],
+                };
+            }
+            v.dir = dir;
+            match dir.neighbor(v.origin, self.map) {
+                Some(next) if next == start.origin && self.splitters[next].is_some() => {
+                    let (_, split) = self.map[next].deflect(dir);
+                    return Segment {
+                        tiles,
+                        end: split.and(self.splitters[next]),
+                    };
+                }
+                Some(next) => v.origin = next,
+                None => {
+                    return Segment { tiles, end: None };
+                }
+            }
+        }
+    }
+
+    /// Returns the number of tiles energized by a beam coming from `start`.
+    fn energized(&self, start: LightVector) -> i64 {
+        let segment = self.walk(start);
+        let mut energized = match segment.end {
+            Some(i) => self.energized[self.component[i]].clone(),
+            None => vec![0; self.words()],
+        };
+        set_bits(&mut energized, &segment.tiles);
+        energized.iter().map(|w| w.count_ones() as i64).sum()
+    }
+}
+
+fn set_bits(words: &mut [u64], bits: &[usize]) {
+    for b in bits {
+        words[b / 64] |= 1 << (b % 64);
+    }
+}
+
+fn union(words: &mut [u64], other: &[u64]) {
+    for (w, o) in words.iter_mut().zip(other) {
+        *w |= o;
+    }
+}
+
+/// Every beam that can come in from the edge of the grid: down each column, up each column,
+/// right along each row and left along each row.
+fn edge_entries(map: &Grid<Mirror>) -> Vec<LightVector> {
+    let (width, height) = (map.width() as i64, map.height() as i64);
+    let mut entries = vec![];
+    for x in 0..width {
+        entries.push(LightVector {
+            origin: (0, x),
+            dir: Dir::S,
+        });
+        entries.push(LightVector {
+            origin: (height - 1, x),
+            dir: Dir::N,
+        });
+    }
+    for y in 0..height {
+        entries.push(LightVector {
+            origin: (y, 0),
+            dir: Dir::E,
+        });
+        entries.push(LightVector {
+            origin: (y, width - 1),
+            dir: Dir::W,
+        });
+    }
+    entries
+}
+
+fn energized_from_edges(input: &Input) -> Vec<(LightVector, i64)> {
+    let graph = SegmentGraph::new(&input.map);
+    edge_entries(&input.map)
+        .into_iter()
+        .map(|v| (v, graph.energized(v)))
+        .collect()
 }
 
 #[aoc(day16, part2)]
 fn solve_part2(input: &Input) -> i64 {
-    let mut max = 0;
-    for x in 0..input.map.num_columns() {
-        max = max.max(trace(
-            input,
-            LightVector {
-                origin: (0, x as i64),
-                dir: Dir::S,
-            },
-        ));
-        max = max.max(trace(
-            input,
-            LightVector {
-                origin: (input.map.num_rows() as i64 - 1, x as i64),
-                dir: Dir::N,
-            },
-        ));
-    }
-    for y in 0..input.map.num_rows() {
-        max = max.max(trace(
-            input,
-            LightVector {
-                origin: (y as i64, 0),
-                dir: Dir::E,
-            },
-        ));
-        max = max.max(trace(
-            input,
-            LightVector {
-                origin: (y as i64, input.map.num_columns() as i64 - 1),
-                dir: Dir::W,
-            },
-        ));
+    energized_from_edges(input)
+        .into_iter()
+        .map(|(_, n)| n)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Parses a grid and returns the number of tiles energized by each beam that can come in from
+/// its edge.
+pub fn edge_energies(input: &str) -> Result<Vec<(LightVector, i64)>, ParseError> {
+    Ok(energized_from_edges(&generate(input)?))
+}
+
+/// Parses a grid and traces the beam coming from `start`.
+pub fn trace_beams(input: &str, start: LightVector) -> Result<Beams, ParseError> {
+    Ok(trace(&generate(input)?, start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = r".|...\....
+|.-.\.....
+.....|-...
+........|.
+..........
+.........\
+..../.\\..
+.-.-/..|..
+.|....-|.\
+..//.|....
+";
+
+    const START: LightVector = LightVector {
+        origin: (0, 0),
+        dir: Dir::E,
+    };
+
+    /// Checks the segment graph against tracing each beam from the edge.
+    fn check_edges(input: &str) {
+        let input = generate(input).unwrap();
+        let graph = SegmentGraph::new(&input.map);
+        for v in edge_entries(&input.map) {
+            assert_eq!(
+                graph.energized(v),
+                trace(&input, v).count(),
+                "{v:?} in\n{}",
+                input.map
+            );
+        }
+    }
+
+    #[test]
+    fn energized_from_edges_example() {
+        check_edges(EXAMPLE);
+        assert_eq!(solve_part2(&generate(EXAMPLE).unwrap()), 51);
+    }
+
+    #[test]
+    fn energized_from_edges_loops() {
+        // The beams leaving each splitter eastward (or southward) come back around to it and
+        // pass straight through.
+        check_edges("/-\\\n\\./");
+        check_edges("/\\\n|.\n\\/");
+        // Splitters on every edge.
+        check_edges("-|-\n|.|\n-|-");
+    }
+
+    #[test]
+    fn energized_from_edges_random() {
+        let mut state = 0x2545f4914f6cdd1du64;
+        let mut next = |n: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % n
+        };
+        for _ in 0..300 {
+            let (width, height) = (1 + next(12), 1 + next(12));
+            let grid: String = (0..height)
+                .map(|_| {
+                    let row: String = (0..width)
+                        .map(|_| match next(8) {
+                            0 => '/',
+                            1 => '\\',
+                            2 => '|',
+                            3 => '-',
+                            _ => '.',
+                        })
+                        .collect();
+                    row + "\n"
+                })
+                .collect();
+            check_edges(&grid);
+        }
+    }
+
+    #[test]
+    fn render_energized_example() {
+        let beams = trace_beams(EXAMPLE, START).unwrap();
+        assert_eq!(beams.count(), 46);
+        assert_eq!(
+            beams.render_energized(),
+            "\
+######....
+.#...#....
+.#...#####
+.#...##...
+.#...##...
+.#...##...
+.#..####..
+########..
+.#######..
+.#...#.#..
+"
+        );
+    }
+
+    #[test]
+    fn render_paths_example() {
+        let beams = trace_beams(EXAMPLE, START).unwrap();
+        assert_eq!(
+            beams.render_paths(),
+            r">|<<<\....
+|v-.\^....
+.v...|->>>
+.v...v^.|.
+.v...v^...
+.v...v^..\
+.v../2\\..
+<->-/vv|..
+.|<<<2-|.\
+.v//.|.v..
+"
+        );
+    }
+
+    #[test]
+    fn ppm() {
+        let beams = trace_beams(EXAMPLE, START).unwrap();
+        for scale in [1, 3] {
+            let image = beams.to_ppm(scale);
+            let header = format!("P6\n{0} {0}\n255\n", 10 * scale);
+            assert!(image.starts_with(header.as_bytes()));
+            assert_eq!(image.len(), header.len() + 10 * scale * 10 * scale * 3);
+            // The top left tile is empty and energized, and the one below it is a splitter that
+            // no beam reaches.
+            let pixels = &image[header.len()..];
+            assert_eq!(pixels[..3], [255, 192, 0]);
+            let below = 10 * scale * scale * 3;
+            assert_eq!(pixels[below..below + 3], [112, 112, 128]);
+        }
     }
-    max
 }
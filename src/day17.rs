@@ -1,275 +1,210 @@
-use crate::Dir;
+use crate::{search, Dir, Grid, ParseError, Plane};
 use aoc_runner_derive::{aoc, aoc_generator};
-use array2d::Array2D;
+use std::fmt::Display;
 
 #[derive(Clone, Debug)]
 struct Input {
-    map: Array2D<u8>,
+    map: Grid<u8>,
+}
+
+/// Parses a heat map, with the heat loss of each block as a single digit.
+pub fn heat_map(input: &str) -> Result<Grid<u8>, ParseError> {
+    Grid::parse(17, input, |c| c.to_digit(10).map(|d| d as u8))
 }
 
 #[aoc_generator(day17)]
-fn generate(input: &str) -> Input {
-    let width = input.lines().next().unwrap().len();
-    let height = input.lines().count();
-    let map = Array2D::from_iter_row_major(
-        input
-            .lines()
-            .map(|line| line.chars().map(|c| c.to_string().parse().unwrap()))
-            .flatten(),
-        height,
-        width,
-    )
-    .unwrap();
-    Input { map }
+fn generate(input: &str) -> Result<Input, ParseError> {
+    Ok(Input {
+        map: heat_map(input)?,
+    })
 }
 
-#[derive(Clone, Debug)]
+/// How far a crucible has to move in a straight line, counted in blocks.
+#[derive(Copy, Clone, Debug)]
+pub struct Runs {
+    /// The crucible can only turn or stop once it has moved at least this far.
+    pub min: u8,
+    /// The crucible has to turn once it has moved this far.
+    pub max: u8,
+}
+
+pub const CRUCIBLE: Runs = Runs { min: 1, max: 3 };
+pub const ULTRA_CRUCIBLE: Runs = Runs { min: 4, max: 10 };
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 struct Cart {
-    pos: (usize, usize),
+    pos: (i64, i64),
     dir: Dir,
-    dir_remaining: u8,
+    /// How many blocks we have moved in `dir` since the last turn.
+    run: u8,
 }
 
-#[derive(Copy, Clone, Default, Debug, Eq, PartialEq)]
-struct DirMap<T> {
-    dirs: [T; 4],
+/// The route a crucible takes across the heat map.
+#[derive(Clone, Debug)]
+pub struct Path<'a> {
+    map: &'a Grid<u8>,
+    /// Every block the crucible passes through, in order, along with the direction it was moving
+    /// when it entered. The starting block has no direction.
+    pub cells: Vec<((i64, i64), Option<Dir>)>,
+    /// The blocks where the crucible changes direction.
+    pub turns: Vec<(i64, i64)>,
+    pub heat_loss: i64,
 }
 
-impl<T: Clone> DirMap<T> {
-    fn filled_with(v: T) -> Self {
-        DirMap {
-            dirs: [v.clone(), v.clone(), v.clone(), v],
+impl Path<'_> {
+    fn new(map: &Grid<u8>, carts: Vec<Cart>, heat_loss: i64) -> Path<'_> {
+        let cells = carts
+            .iter()
+            .enumerate()
+            .map(|(i, cart)| (cart.pos, (i > 0).then_some(cart.dir)))
+            .collect();
+        let turns = carts
+            .windows(2)
+            .skip(1)
+            .filter(|w| w[0].dir != w[1].dir)
+            .map(|w| w[0].pos)
+            .collect();
+        Path {
+            map,
+            cells,
+            turns,
+            heat_loss,
         }
     }
 }
 
-impl<T> std::ops::Index<Dir> for DirMap<T> {
-    type Output = T;
-    fn index(&self, index: Dir) -> &Self::Output {
-        &self.dirs[match index {
-            Dir::N => 0,
-            Dir::S => 1,
-            Dir::E => 2,
-            Dir::W => 3,
-        }]
+impl Display for Path<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut tiles = self
+            .map
+            .map(|loss| char::from_digit(*loss as u32, 10).unwrap_or('?'));
+        for (pos, dir) in &self.cells {
+            if let Some(dir) = dir {
+                tiles[*pos] = match dir {
+                    Dir::N => '^',
+                    Dir::S => 'v',
+                    Dir::E => '>',
+                    Dir::W => '<',
+                };
+            }
+        }
+        writeln!(
+            f,
+            "heat loss = {}, turns = {}",
+            self.heat_loss,
+            self.turns.len()
+        )?;
+        write!(f, "{}", tiles)
     }
 }
 
-impl<T> std::ops::IndexMut<Dir> for DirMap<T> {
-    fn index_mut(&mut self, index: Dir) -> &mut Self::Output {
-        &mut self.dirs[match index {
-            Dir::N => 0,
-            Dir::S => 1,
-            Dir::E => 2,
-            Dir::W => 3,
-        }]
-    }
+/// Returns the route that loses the least heat getting a crucible from the top left to the bottom
+/// right of the map, if it can get there at all.
+pub fn best_path(map: &Grid<u8>, runs: Runs) -> Option<Path<'_>> {
+    let target = (map.height() as i64 - 1, map.width() as i64 - 1);
+    // Every block costs at least this much, which keeps the heuristic from overestimating.
+    let min_loss = map.iter().map(|(_, loss)| *loss as i64).min().unwrap_or(0);
+    let successors = |cart: &Cart| {
+        let straight = (cart.run < runs.max).then_some((cart.dir, cart.run + 1));
+        let turns = (cart.run >= runs.min)
+            .then_some([(cart.dir.cw(), 1), (cart.dir.ccw(), 1)])
+            .into_iter()
+            .flatten();
+        straight
+            .into_iter()
+            .chain(turns)
+            .filter_map(|(dir, run)| {
+                let pos = dir.neighbor(cart.pos, map)?;
+                Some((Cart { pos, dir, run }, map[pos] as i64))
+            })
+            .collect::<Vec<_>>()
+    };
+    // For some reason, the start location does not contribute to heat loss.
+    let starts = [Dir::S, Dir::E].map(|dir| Cart {
+        pos: (0, 0),
+        dir,
+        run: 0,
+    });
+    search::astar(
+        starts,
+        successors,
+        |cart| ((target.0 - cart.pos.0) + (target.1 - cart.pos.1)) * min_loss,
+        |cart| cart.pos == target && cart.run >= runs.min,
+    )
+    .map(|(carts, loss)| Path::new(map, carts, loss))
 }
 
 #[aoc(day17, part1)]
-fn solve_part1(input: &Input) -> i64 {
-    // Each element represents the smallest cost traveling through the current position in each
-    // different direction, broken down by number of remaining moves in the current direction.
-    // sps[(y, x)][Dir::N][0] -> traveling north through (y, x) with 0 straight moves left (i.e.,
-    // the next move must be a turn).
-    let mut sps = Array2D::<DirMap<[Option<i64>; 3]>>::filled_with(
-        Default::default(),
-        input.map.num_rows(),
-        input.map.num_columns(),
-    );
-
-    let mut current = vec![];
-
-    for dir in [Dir::S, Dir::E].into_iter() {
-        current.push(Cart {
-            pos: (0, 0),
-            dir,
-            dir_remaining: 2,
-        });
-    }
-
-    sps[(0, 0)] = DirMap::filled_with([
-        None,
-        None,
-        // For some reason, the start location does not contribute to heat loss.
-        Some(0),
-    ]);
-
-    let mut next = vec![];
-    while !current.is_empty() {
-        for cart in current.drain(..) {
-            let partial = sps[cart.pos][cart.dir][cart.dir_remaining as usize].unwrap();
-            for dir in [cart.dir.cw(), cart.dir.ccw()].into_iter() {
-                if let Some(pos) = dir.neighbor((cart.pos.0 as i64, cart.pos.1 as i64), &input.map)
-                {
-                    let pos = (pos.0 as usize, pos.1 as usize);
-                    let next_cart = Cart {
-                        pos,
-                        dir,
-                        dir_remaining: 2,
-                    };
-                    let sp = &mut sps[pos][dir][2];
-                    match sp {
-                        None => {
-                            *sp = Some(partial + input.map[pos] as i64);
-                            next.push(next_cart);
-                        }
-                        Some(existing_cost) => {
-                            if (partial + input.map[pos] as i64) < *existing_cost {
-                                *existing_cost = partial + input.map[pos] as i64;
-                                next.push(next_cart);
-                            }
-                        }
-                    }
-                }
-            }
-            if cart.dir_remaining > 0 {
-                if let Some(pos) = cart
-                    .dir
-                    .neighbor((cart.pos.0 as i64, cart.pos.1 as i64), &input.map)
-                {
-                    let pos = (pos.0 as usize, pos.1 as usize);
-                    let next_cart = Cart {
-                        pos,
-                        dir: cart.dir,
-                        dir_remaining: cart.dir_remaining - 1,
-                    };
-                    let sp = &mut sps[pos][cart.dir][cart.dir_remaining as usize - 1];
-                    match sp {
-                        None => {
-                            *sp = Some(partial + input.map[pos] as i64);
-                            next.push(next_cart);
-                        }
-                        Some(existing_cost) => {
-                            if (partial + input.map[pos] as i64) < *existing_cost {
-                                *existing_cost = partial + input.map[pos] as i64;
-                                next.push(next_cart);
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        current.extend(next.drain(..));
-    }
-
-    let target = (input.map.num_rows() - 1, input.map.num_columns() - 1);
-
-    let min = [Dir::S, Dir::E]
-        .into_iter()
-        .map(|dir| sps[target][dir].iter())
-        .flatten()
-        .filter_map(|sp| *sp)
-        .min()
-        .unwrap();
-
-    min
+fn solve_part1(input: &Input) -> Option<i64> {
+    best_path(&input.map, CRUCIBLE).map(|path| path.heat_loss)
 }
 
 #[aoc(day17, part2)]
-fn solve_part2(input: &Input) -> i64 {
-    // This is the same as part one but we need to track up to 10 straight moves remaining.
-    let mut sps = Array2D::<DirMap<[Option<i64>; 10]>>::filled_with(
-        Default::default(),
-        input.map.num_rows(),
-        input.map.num_columns(),
-    );
-
-    let mut current = vec![];
-
-    for dir in [Dir::S, Dir::E].into_iter() {
-        current.push(Cart {
-            pos: (0, 0),
-            dir,
-            dir_remaining: 9,
-        });
-    }
-
-    sps[(0, 0)] = DirMap::filled_with([
-        None,
-        None,
-        None,
-        None,
-        None,
-        None,
-        None,
-        None,
-        None,
-        Some(0),
-    ]);
+fn solve_part2(input: &Input) -> Option<i64> {
+    best_path(&input.map, ULTRA_CRUCIBLE).map(|path| path.heat_loss)
+}
 
-    let mut next = vec![];
-    while !current.is_empty() {
-        for cart in current.drain(..) {
-            // This will have been filled by the previous iteration of the outer loop.
-            let partial = sps[cart.pos][cart.dir][cart.dir_remaining as usize].unwrap();
-            if cart.dir_remaining < 7 {
-                for dir in [cart.dir.cw(), cart.dir.ccw()].into_iter() {
-                    if let Some(pos) =
-                        dir.neighbor((cart.pos.0 as i64, cart.pos.1 as i64), &input.map)
-                    {
-                        let pos = (pos.0 as usize, pos.1 as usize);
-                        let next_cart = Cart {
-                            pos,
-                            dir,
-                            dir_remaining: 9,
-                        };
-                        let sp = &mut sps[pos][dir][9];
-                        match sp {
-                            None => {
-                                *sp = Some(partial + input.map[pos] as i64);
-                                next.push(next_cart);
-                            }
-                            Some(existing_cost) => {
-                                if (partial + input.map[pos] as i64) < *existing_cost {
-                                    *existing_cost = partial + input.map[pos] as i64;
-                                    next.push(next_cart);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            if cart.dir_remaining > 0 {
-                if let Some(pos) = cart
-                    .dir
-                    .neighbor((cart.pos.0 as i64, cart.pos.1 as i64), &input.map)
-                {
-                    let pos = (pos.0 as usize, pos.1 as usize);
-                    let next_cart = Cart {
-                        pos,
-                        dir: cart.dir,
-                        dir_remaining: cart.dir_remaining - 1,
-                    };
-                    let sp = &mut sps[pos][cart.dir][cart.dir_remaining as usize - 1];
-                    match sp {
-                        None => {
-                            *sp = Some(partial + input.map[pos] as i64);
-                            next.push(next_cart);
-                        }
-                        Some(existing_cost) => {
-                            if (partial + input.map[pos] as i64) < *existing_cost {
-                                *existing_cost = partial + input.map[pos] as i64;
-                                next.push(next_cart);
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        current.extend(next.drain(..));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+2413432311323
+3215453535623
+3255245654254
+3446585845452
+4546657867536
+1438598798454
+4457876987766
+3637877979653
+4654967986887
+4564679986453
+1224686865563
+2546548887735
+4322674655533";
+
+    #[test]
+    fn renders_example_route() {
+        let map = heat_map(EXAMPLE).unwrap();
+        let path = best_path(&map, CRUCIBLE).unwrap();
+        assert_eq!(path.heat_loss, 102);
+        assert_eq!(
+            path.turns,
+            [
+                (0, 2),
+                (1, 2),
+                (1, 5),
+                (0, 5),
+                (0, 8),
+                (2, 8),
+                (2, 10),
+                (4, 10),
+                (4, 11),
+                (7, 11),
+                (7, 12),
+                (10, 12),
+                (10, 11),
+                (12, 11),
+            ]
+        );
+        assert_eq!(
+            path.to_string(),
+            "\
+heat loss = 102, turns = 14
+2>>34^>>>1323
+32v>>>35v5623
+32552456v>>54
+3446585845v52
+4546657867v>6
+14385987984v4
+44578769877v6
+36378779796v>
+465496798688v
+456467998645v
+12246868655<v
+25465488877v5
+43226746555v>
+"
+        );
     }
-
-    let target = (input.map.num_rows() - 1, input.map.num_columns() - 1);
-    let min = [Dir::S, Dir::E]
-        .into_iter()
-        // It's impossible to end with a dir_remaining of > 7 since we must end after moving at
-        // least 4 squares.
-        .map(|dir| sps[target][dir].iter().take(7))
-        .flatten()
-        .filter_map(|sp| *sp)
-        .min()
-        .unwrap();
-
-    min
 }
@@ -1,4 +1,4 @@
-use crate::Dir;
+use crate::{lines, Dir, ParseError};
 use aoc_runner_derive::{aoc, aoc_generator};
 use regex::Regex;
 
@@ -15,45 +15,48 @@ struct Input {
 }
 
 #[aoc_generator(day18)]
-fn generate(input: &str) -> Input {
+fn generate(input: &str) -> Result<Input, ParseError> {
     let re = Regex::new(r"^([UDLR]) (\d+) \(#([0-9a-f]{5})([0-9a-f])\)$").unwrap();
-    let (part1_instructions, part2_instructions): (Vec<_>, Vec<_>) = input
-        .lines()
+    let (part1_instructions, part2_instructions): (Vec<_>, Vec<_>) = lines(18, input)
         .map(|line| {
-            let caps = re.captures(line).unwrap();
+            let caps = re
+                .captures(line.text)
+                .ok_or_else(|| line.error(line.text, "expected \"<dir> <len> (#<color>)\""))?;
             let dir = match &caps[1] {
                 "U" => Dir::N,
                 "D" => Dir::S,
                 "L" => Dir::W,
                 "R" => Dir::E,
-                other => panic!("invalid direction {}", other),
+                _ => unreachable!(),
             };
-            let distance = caps[2].parse::<u32>().unwrap();
+            let distance = line.parse::<u32>(&caps[2])?;
             let part2_distance = u32::from_str_radix(&caps[3], 16).unwrap();
             let part2_dir = match &caps[4] {
                 "0" => Dir::E,
                 "1" => Dir::S,
                 "2" => Dir::W,
                 "3" => Dir::N,
-                other => panic!("invalid hex direction {}", other),
+                other => return Err(line.error(other, "invalid hex direction")),
             };
-            (
+            Ok((
                 Instruction { dir, distance },
                 Instruction {
                     dir: part2_dir,
                     distance: part2_distance,
                 },
-            )
+            ))
         })
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
         .unzip();
 
-    Input {
+    Ok(Input {
         part1_instructions,
         part2_instructions,
-    }
+    })
 }
 
-fn solve(input: &Vec<Instruction>) -> i64 {
+fn solve(input: &[Instruction]) -> i64 {
     let mut y = 0;
     let mut total = 0;
     for i in input {
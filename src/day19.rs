@@ -1,11 +1,17 @@
+use crate::{lines, Line, ParseError};
 use aoc_runner_derive::{aoc, aoc_generator};
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Display;
+use std::iter::Peekable;
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 enum Bound {
     Gt(u32),
     Lt(u32),
+    Ge(u32),
+    Le(u32),
+    Eq(u32),
 }
 
 impl Bound {
@@ -13,16 +19,69 @@ impl Bound {
         match self {
             Bound::Gt(b) => val > *b,
             Bound::Lt(b) => val < *b,
+            Bound::Ge(b) => val >= *b,
+            Bound::Le(b) => val <= *b,
+            Bound::Eq(b) => val == *b,
+        }
+    }
+
+    /// Returns the smallest and largest values that satisfy the bound, if any do.
+    fn range(&self) -> Option<(u32, u32)> {
+        match *self {
+            Bound::Gt(b) => Some((b.checked_add(1)?, u32::MAX)),
+            Bound::Lt(b) => Some((0, b.checked_sub(1)?)),
+            Bound::Ge(b) => Some((b, u32::MAX)),
+            Bound::Le(b) => Some((0, b)),
+            Bound::Eq(b) => Some((b, b)),
         }
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
-enum Attribute {
-    X,
-    M,
-    A,
-    S,
+/// An index into `Schema::attributes`.
+type Attribute = usize;
+
+/// The attributes that parts have, and the values they can take.
+///
+/// These are `x`, `m`, `a` and `s` from 1 to 4000 unless the input declares its own in a header
+/// before the workflows, which can be separated from them by a blank line:
+///
+/// ```text
+/// attributes: width, height
+/// domain: 0..=100
+///
+/// in{width>50&height>50:A,R}
+///
+/// {width=60,height=70}
+/// ```
+///
+/// Either line of the header can be left out to keep the default.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Schema {
+    attributes: Vec<String>,
+    /// The smallest and largest value of every attribute.
+    domain: (u32, u32),
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Schema {
+            attributes: ["x", "m", "a", "s"].map(String::from).to_vec(),
+            domain: (1, 4000),
+        }
+    }
+}
+
+impl Schema {
+    fn attribute(&self, name: &str) -> Option<Attribute> {
+        self.attributes.iter().position(|a| a == name)
+    }
+
+    /// Returns every part there could be.
+    fn everything(&self) -> PossibleParts {
+        PossibleParts {
+            ranges: vec![self.domain; self.attributes.len()],
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -35,45 +94,37 @@ enum Action {
 impl Action {
     fn goto(&self) -> Option<&str> {
         match self {
-            Action::Goto(s) => Some(&s),
+            Action::Goto(s) => Some(s),
             _ => None,
         }
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
-struct Rule {
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+struct Condition {
     attribute: Attribute,
     bound: Bound,
-    action: Action,
 }
 
-impl Rule {
-    fn applies(&self, p: &Part) -> bool {
-        let result = self.bound.satisfies(p.attribute(self.attribute));
-        result
-    }
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Rule {
+    /// The rule applies only if all of these hold.
+    conditions: Vec<Condition>,
+    action: Action,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 struct Part {
-    x: u32,
-    m: u32,
-    a: u32,
-    s: u32,
+    /// The value of each attribute, in the order they are declared in the schema.
+    values: Vec<u32>,
 }
 
 impl Part {
     fn score(&self) -> u64 {
-        self.x as u64 + self.m as u64 + self.a as u64 + self.s as u64
+        self.values.iter().map(|v| *v as u64).sum()
     }
     fn attribute(&self, a: Attribute) -> u32 {
-        match a {
-            Attribute::X => self.x,
-            Attribute::M => self.m,
-            Attribute::A => self.a,
-            Attribute::S => self.s,
-        }
+        self.values[a]
     }
 }
 
@@ -83,79 +134,308 @@ struct RuleSet {
     default_action: Action,
 }
 
+impl RuleSet {
+    /// Returns the action taken by the rule at index `i`, or the default action if `i` is the
+    /// number of rules.
+    fn action(&self, i: usize) -> &Action {
+        self.rules
+            .get(i)
+            .map_or(&self.default_action, |r| &r.action)
+    }
+
+    /// Splits up `pp` by which rule applies to each part, returning the pieces along with the
+    /// index of the rule, as in `action`.
+    fn route(&self, pp: PossibleParts) -> Vec<(usize, PossibleParts)> {
+        let mut routed = vec![];
+        let mut remaining = vec![pp];
+        for (i, rule) in self.rules.iter().enumerate() {
+            let mut next = vec![];
+            for pp in remaining {
+                routed.extend(pp.include(rule).map(|reduced| (i, reduced)));
+                next.extend(pp.exclude(rule));
+            }
+            remaining = next;
+        }
+        routed.extend(remaining.into_iter().map(|pp| (self.rules.len(), pp)));
+        routed
+    }
+}
+
+/// Where a compiled instruction sends a part.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Jump {
+    To(usize),
+    Accept,
+    Reject,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Op {
+    /// Jumps to `pass` if the part satisfies the condition, and to `fail` otherwise.
+    Test {
+        condition: Condition,
+        pass: Jump,
+        fail: Jump,
+    },
+    Always(Jump),
+}
+
+/// The workflows compiled down to a flat decision tree, with workflow names resolved to the
+/// positions of their first instruction.
+#[derive(Clone, Default, Debug)]
+struct Program {
+    code: Vec<Op>,
+    start: usize,
+}
+
+impl Program {
+    /// Compiles the workflows, which must already have been checked for missing targets and
+    /// cycles by `analyze`.
+    fn compile(workflows: &HashMap<String, RuleSet>) -> Self {
+        let mut offsets = HashMap::new();
+        let mut offset = 0;
+        for (name, ruleset) in workflows {
+            offsets.insert(name.as_str(), offset);
+            offset += ruleset
+                .rules
+                .iter()
+                .map(|r| r.conditions.len())
+                .sum::<usize>()
+                + 1;
+        }
+        let jump = |action: &Action| match action {
+            Action::Goto(name) => Jump::To(offsets[name.as_str()]),
+            Action::Accept => Jump::Accept,
+            Action::Reject => Jump::Reject,
+        };
+        let mut code = vec![Op::Always(Jump::Reject); offset];
+        for (name, ruleset) in workflows {
+            let mut pc = offsets[name.as_str()];
+            for rule in &ruleset.rules {
+                // Each condition of a conjunction passes on to the next one, and all of them fall
+                // through to the next rule if they fail.
+                let next_rule = pc + rule.conditions.len();
+                for (i, condition) in rule.conditions.iter().enumerate() {
+                    code[pc] = Op::Test {
+                        condition: *condition,
+                        pass: if i + 1 == rule.conditions.len() {
+                            jump(&rule.action)
+                        } else {
+                            Jump::To(pc + 1)
+                        },
+                        fail: Jump::To(next_rule),
+                    };
+                    pc += 1;
+                }
+            }
+            code[pc] = Op::Always(jump(&ruleset.default_action));
+        }
+        Program {
+            code,
+            start: offsets["in"],
+        }
+    }
+
+    fn accepts(&self, part: &Part) -> bool {
+        let mut pc = self.start;
+        loop {
+            let jump = match self.code[pc] {
+                Op::Test {
+                    condition,
+                    pass,
+                    fail,
+                } => {
+                    if condition
+                        .bound
+                        .satisfies(part.attribute(condition.attribute))
+                    {
+                        pass
+                    } else {
+                        fail
+                    }
+                }
+                Op::Always(jump) => jump,
+            };
+            match jump {
+                Jump::To(next) => pc = next,
+                Jump::Accept => return true,
+                Jump::Reject => return false,
+            }
+        }
+    }
+}
+
 #[derive(Default)]
 struct Input {
+    schema: Schema,
     rules: HashMap<String, RuleSet>,
+    program: Program,
     parts: Vec<Part>,
 }
 
-#[aoc_generator(day19)]
-fn generate(input: &str) -> Input {
-    let ruleset_re = Regex::new(r"^(\w+)\{((?:[xmas][<>][0-9]+:\w+,)+)(\w+)\}").unwrap();
-    let rule_re = Regex::new(r"([xmas])([<>])([0-9]+):(\w+)").unwrap();
-    let rules = input
-        .lines()
-        .take_while(|line| !line.trim().is_empty())
+/// Parses an `attributes: x, m, a, s` or `domain: 1..=4000` line from the header.
+fn parse_header(line: &Line, schema: &mut Schema) -> Result<(), ParseError> {
+    let (key, value) = line.split_once(":")?;
+    match key {
+        "attributes" => {
+            schema.attributes.clear();
+            for name in value.split(',').map(str::trim) {
+                if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                    return Err(line.error(name, "invalid attribute name"));
+                }
+                if schema.attribute(name).is_some() {
+                    return Err(line.error(name, "duplicate attribute"));
+                }
+                schema.attributes.push(name.to_string());
+            }
+        }
+        "domain" => {
+            let value = value.trim();
+            let (min, max) = value
+                .split_once("..=")
+                .ok_or_else(|| line.error(value, "expected \"min..=max\""))?;
+            schema.domain = (line.parse(min)?, line.parse(max)?);
+            if schema.domain.0 > schema.domain.1 {
+                return Err(line.error(value, "empty domain"));
+            }
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+fn parse_part(line: &Line, schema: &Schema) -> Result<Part, ParseError> {
+    let fields = line
+        .text
+        .strip_prefix('{')
+        .and_then(|s| s.strip_suffix('}'))
+        .ok_or_else(|| line.error(line.text, "invalid part"))?;
+    let mut values = vec![None; schema.attributes.len()];
+    for field in fields.split(',') {
+        let (name, value) = field
+            .split_once('=')
+            .ok_or_else(|| line.error(field, "expected \"name=value\""))?;
+        let a = schema
+            .attribute(name)
+            .ok_or_else(|| line.error(name, "unknown attribute"))?;
+        if values[a].is_some() {
+            return Err(line.error(name, "duplicate attribute"));
+        }
+        let value = line.parse(value)?;
+        if !(schema.domain.0..=schema.domain.1).contains(&value) {
+            return Err(line.error(field, "value out of range"));
+        }
+        values[a] = Some(value);
+    }
+    let values = values
+        .into_iter()
+        .zip(&schema.attributes)
+        .map(|(v, name)| v.ok_or_else(|| line.error(line.text, format!("missing {name}"))))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Part { values })
+}
+
+/// Parses the header and workflows at the start of the input, up to the blank line before the
+/// parts.
+fn parse_workflows<'a, I>(
+    lines: &mut Peekable<I>,
+) -> Result<(Schema, HashMap<String, RuleSet>), ParseError>
+where
+    I: Iterator<Item = Line<'a>>,
+{
+    // The input may start with a header declaring the attributes of parts and their domain.
+    let mut schema = Schema::default();
+    let mut header = false;
+    while let Some(line) = lines
+        .next_if(|line| line.text.starts_with("attributes:") || line.text.starts_with("domain:"))
+    {
+        parse_header(&line, &mut schema)?;
+        header = true;
+    }
+    if header {
+        lines.next_if(|line| line.text.trim().is_empty());
+    }
+
+    let ruleset_re = Regex::new(r"^(\w+)\{((?:[^,{}]+:\w+,)+)(\w+)\}").unwrap();
+    let condition_re = Regex::new(r"^(\w+)(<=|>=|==|<|>)([0-9]+)$").unwrap();
+    let rules = lines
+        .by_ref()
+        .take_while(|line| !line.text.trim().is_empty())
         .map(|line| {
-            let caps = ruleset_re.captures(line).unwrap();
+            let caps = ruleset_re
+                .captures(line.text)
+                .ok_or_else(|| line.error(line.text, "invalid workflow"))?;
             let name = caps[1].to_string();
-            let rules = rule_re
-                .captures_iter(&caps[2])
-                .map(|c| {
-                    let attribute = match &c[1] {
-                        "x" => Attribute::X,
-                        "m" => Attribute::M,
-                        "a" => Attribute::A,
-                        "s" => Attribute::S,
-                        other => panic!("invalid attribute {other}"),
-                    };
-                    let bound = match &c[2] {
-                        "<" => Bound::Lt(c[3].parse().unwrap()),
-                        ">" => Bound::Gt(c[3].parse().unwrap()),
-                        _ => panic!(),
-                    };
-                    let action = match &c[4] {
+            let rules = caps
+                .get(2)
+                .unwrap()
+                .as_str()
+                .split_terminator(',')
+                .map(|rule| {
+                    let (conditions, target) = rule.split_once(':').unwrap();
+                    let conditions = conditions
+                        .split('&')
+                        .map(|condition| {
+                            let c = condition_re
+                                .captures(condition)
+                                .ok_or_else(|| line.error(condition, "invalid condition"))?;
+                            let name = c.get(1).unwrap().as_str();
+                            let attribute = schema
+                                .attribute(name)
+                                .ok_or_else(|| line.error(name, "unknown attribute"))?;
+                            let value = line.parse(c.get(3).unwrap().as_str())?;
+                            let bound = match &c[2] {
+                                "<" => Bound::Lt(value),
+                                ">" => Bound::Gt(value),
+                                "<=" => Bound::Le(value),
+                                ">=" => Bound::Ge(value),
+                                "==" => Bound::Eq(value),
+                                _ => unreachable!(),
+                            };
+                            Ok(Condition { attribute, bound })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let action = match target {
                         "A" => Action::Accept,
                         "R" => Action::Reject,
                         other => Action::Goto(other.to_string()),
                     };
-                    Rule {
-                        attribute,
-                        bound,
-                        action,
-                    }
+                    Ok(Rule { conditions, action })
                 })
-                .collect::<Vec<_>>();
+                .collect::<Result<Vec<_>, _>>()?;
             let default_action = match &caps[3] {
                 "A" => Action::Accept,
                 "R" => Action::Reject,
                 other => Action::Goto(other.to_string()),
             };
-            (
+            Ok((
                 name,
                 RuleSet {
                     rules,
                     default_action,
                 },
-            )
-        })
-        .collect::<HashMap<_, _>>();
-    let part_re = Regex::new(r"^\{x=([0-9]+),m=([0-9]+),a=([0-9]+),s=([0-9]+)\}").unwrap();
-    let parts = input
-        .lines()
-        .skip_while(|line| !line.trim().is_empty())
-        .skip(1)
-        .map(|line| {
-            let caps = part_re.captures(line).unwrap();
-            Part {
-                x: caps[1].parse().unwrap(),
-                m: caps[2].parse().unwrap(),
-                a: caps[3].parse().unwrap(),
-                s: caps[4].parse().unwrap(),
-            }
+            ))
         })
-        .collect::<Vec<_>>();
-    Input { rules, parts }
+        .collect::<Result<HashMap<_, _>, _>>()?;
+    Ok((schema, rules))
+}
+
+#[aoc_generator(day19)]
+fn generate(input: &str) -> Result<Input, ParseError> {
+    let mut lines = lines(19, input).peekable();
+    let (schema, rules) = parse_workflows(&mut lines)?;
+    if let Some(issue) = analyze(&rules, &schema).into_iter().find(Issue::is_fatal) {
+        return Err(ParseError::input(19, issue.to_string()));
+    }
+    let parts = lines
+        .map(|line| parse_part(&line, &schema))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Input {
+        program: Program::compile(&rules),
+        schema,
+        rules,
+        parts,
+    })
 }
 
 #[aoc(day19, part1)]
@@ -163,137 +443,419 @@ fn solve_part1(input: &Input) -> u64 {
     input
         .parts
         .iter()
-        .map(|part| {
-            let mut action = Action::Goto("in".to_string());
-            'outer: while let Some(rule_name) = action.goto() {
-                let ruleset = &input.rules[rule_name];
-                for rule in &ruleset.rules {
-                    if rule.applies(part) {
-                        action = rule.action.clone();
-                        continue 'outer;
-                    }
-                }
-
-                action = ruleset.default_action.clone();
-            }
-            match action {
-                Action::Accept => {
-                    return part.score();
-                }
-                Action::Reject => {
-                    return 0;
-                }
-                _ => unreachable!(),
-            }
-        })
+        .filter(|part| input.program.accepts(part))
+        .map(|part| part.score())
         .sum::<u64>()
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+/// A box of parts, given by the smallest and largest value of each attribute.
+#[derive(Clone, PartialEq, Eq, Debug)]
 struct PossibleParts {
-    x: (u32, u32),
-    m: (u32, u32),
-    a: (u32, u32),
-    s: (u32, u32),
+    ranges: Vec<(u32, u32)>,
 }
 
 impl PossibleParts {
-    fn new() -> Self {
-        PossibleParts {
-            x: (1, 4000),
-            m: (1, 4000),
-            a: (1, 4000),
-            s: (1, 4000),
-        }
+    /// Returns the parts whose value of `a` is between `min` and `max` inclusive, if there are
+    /// any.
+    fn restrict(&self, a: Attribute, (min, max): (u32, u32)) -> Option<Self> {
+        let mut reduced = self.clone();
+        let ta = &mut reduced.ranges[a];
+        *ta = (ta.0.max(min), ta.1.min(max));
+        (ta.0 <= ta.1).then_some(reduced)
     }
 
+    /// Returns the parts that `rule` applies to, if there are any.
     fn include(&self, rule: &Rule) -> Option<Self> {
-        let mut reduced = self.clone();
-        let ta = match rule.attribute {
-            Attribute::X => &mut reduced.x,
-            Attribute::M => &mut reduced.m,
-            Attribute::A => &mut reduced.a,
-            Attribute::S => &mut reduced.s,
-        };
-        match rule.bound {
-            Bound::Gt(min) => {
-                if ta.1 < min {
-                    return None;
-                } else {
-                    ta.0 = min + 1;
-                }
+        rule.conditions.iter().try_fold(self.clone(), |pp, c| {
+            pp.restrict(c.attribute, c.bound.range()?)
+        })
+    }
+
+    /// Returns the parts that `rule` doesn't apply to, as a set of non-overlapping boxes.
+    fn exclude(&self, rule: &Rule) -> Vec<Self> {
+        // A part fails a conjunction if it fails any of its conditions. To keep the pieces from
+        // overlapping, each part goes in the piece for the first condition it fails.
+        let mut excluded = vec![];
+        let mut passed = self.clone();
+        for c in &rule.conditions {
+            let Some((min, max)) = c.bound.range() else {
+                excluded.push(passed);
+                return excluded;
+            };
+            if min > 0 {
+                excluded.extend(passed.restrict(c.attribute, (0, min - 1)));
             }
-            Bound::Lt(max) => {
-                if ta.0 > max {
-                    return None;
-                } else {
-                    ta.1 = max - 1;
-                }
+            if max < u32::MAX {
+                excluded.extend(passed.restrict(c.attribute, (max + 1, u32::MAX)));
+            }
+            match passed.restrict(c.attribute, (min, max)) {
+                Some(pp) => passed = pp,
+                None => return excluded,
             }
         }
-        Some(reduced)
+        excluded
     }
 
-    fn exclude(&self, rule: &Rule) -> Option<Self> {
-        let mut reduced = self.clone();
-        let ta = match rule.attribute {
-            Attribute::X => &mut reduced.x,
-            Attribute::M => &mut reduced.m,
-            Attribute::A => &mut reduced.a,
-            Attribute::S => &mut reduced.s,
-        };
-        match rule.bound {
-            Bound::Gt(min) => {
-                if ta.0 >= min {
-                    return None;
-                } else {
-                    ta.1 = min;
-                }
+    /// Returns the number of parts in the box, or None if there are too many to count.
+    fn count(&self) -> Option<u128> {
+        self.ranges.iter().try_fold(1u128, |n, (min, max)| {
+            n.checked_mul((max - min) as u128 + 1)
+        })
+    }
+
+    /// Like `count`, but for when the result is expected to fit in a u64.
+    fn count_u64(&self) -> Option<u64> {
+        self.count()?.try_into().ok()
+    }
+}
+
+/// A problem with a set of workflows, found by `analyze`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Issue {
+    /// There is no `in` workflow to start from.
+    MissingStart,
+    /// `workflow` sends parts to `target`, which doesn't exist.
+    MissingTarget { workflow: String, target: String },
+    /// No part can ever reach `workflow` from `in`.
+    Unreachable { workflow: String },
+    /// The rule at `index` in `workflow` never applies to any part that reaches it. An index equal
+    /// to the number of rules refers to the default action.
+    DeadRule { workflow: String, index: usize },
+    /// Some parts are sent around these workflows forever, in this order.
+    Cycle { workflows: Vec<String> },
+}
+
+impl Issue {
+    /// Returns true if this issue makes it impossible to sort some parts.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            Issue::MissingStart | Issue::MissingTarget { .. } | Issue::Cycle { .. }
+        )
+    }
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Issue::MissingStart => write!(f, "there is no \"in\" workflow"),
+            Issue::MissingTarget { workflow, target } => {
+                write!(
+                    f,
+                    "workflow {workflow} sends parts to {target}, which doesn't exist"
+                )
+            }
+            Issue::Unreachable { workflow } => write!(f, "workflow {workflow} is unreachable"),
+            Issue::DeadRule { workflow, index } => {
+                write!(f, "rule {} of workflow {workflow} never applies", index + 1)
             }
-            Bound::Lt(max) => {
-                if ta.1 <= max {
-                    return None;
-                } else {
-                    ta.0 = max;
+            Issue::Cycle { workflows } => {
+                write!(
+                    f,
+                    "parts can loop forever through {}",
+                    workflows.join(" -> ")
+                )
+            }
+        }
+    }
+}
+
+/// Checks the workflows for mistakes, by following every possible part through them the same way
+/// as part 2.
+fn analyze(workflows: &HashMap<String, RuleSet>, schema: &Schema) -> Vec<Issue> {
+    let mut issues = vec![];
+    let mut names = workflows.keys().collect::<Vec<_>>();
+    names.sort();
+    for name in &names {
+        let ruleset = &workflows[*name];
+        let actions = ruleset.rules.iter().map(|r| &r.action);
+        for action in actions.chain([&ruleset.default_action]) {
+            match action.goto() {
+                Some(target) if !workflows.contains_key(target) => {
+                    issues.push(Issue::MissingTarget {
+                        workflow: name.to_string(),
+                        target: target.to_string(),
+                    })
                 }
+                _ => {}
             }
         }
-        Some(reduced)
+    }
+    if !workflows.contains_key("in") {
+        issues.push(Issue::MissingStart);
+        return issues;
     }
 
-    fn count(&self) -> u64 {
-        (self.x.1 - self.x.0 + 1) as u64
-            * (self.m.1 - self.m.0 + 1) as u64
-            * (self.a.1 - self.a.0 + 1) as u64
-            * (self.s.1 - self.s.0 + 1) as u64
+    // For each workflow that some part reaches, which of its rules apply to some part.
+    let mut fired = HashMap::<&str, Vec<bool>>::new();
+    let mut cycles = BTreeSet::new();
+    // Each set of parts is tracked along with the workflows it has been through, so that we can
+    // tell when it comes back around.
+    let mut current = vec![(vec!["in"], schema.everything())];
+    while let Some((path, pp)) = current.pop() {
+        let name = *path.last().unwrap();
+        let ruleset = &workflows[name];
+        let fired = fired
+            .entry(name)
+            .or_insert_with(|| vec![false; ruleset.rules.len() + 1]);
+        for (i, pp) in ruleset.route(pp) {
+            fired[i] = true;
+            let Some(target) = ruleset
+                .action(i)
+                .goto()
+                .filter(|t| workflows.contains_key(*t))
+            else {
+                continue;
+            };
+            if let Some(start) = path.iter().position(|n| *n == target) {
+                // Start the cycle from its first workflow alphabetically, so that each one is
+                // only reported once.
+                let mut cycle = path[start..].to_vec();
+                let first = (0..cycle.len()).min_by_key(|i| cycle[*i]).unwrap();
+                cycle.rotate_left(first);
+                cycles.insert(cycle);
+            } else {
+                let mut path = path.clone();
+                path.push(workflows.get_key_value(target).unwrap().0);
+                current.push((path, pp));
+            }
+        }
+    }
+
+    for name in &names {
+        match fired.get(name.as_str()) {
+            None => issues.push(Issue::Unreachable {
+                workflow: name.to_string(),
+            }),
+            Some(fired) => issues.extend(fired.iter().enumerate().filter(|(_, f)| !**f).map(
+                |(index, _)| Issue::DeadRule {
+                    workflow: name.to_string(),
+                    index,
+                },
+            )),
+        }
     }
+    issues.extend(cycles.into_iter().map(|cycle| Issue::Cycle {
+        workflows: cycle.into_iter().map(String::from).collect(),
+    }));
+    issues
+}
+
+/// Parses the workflows at the start of an input and returns every issue with them, including the
+/// ones that would stop the input from being parsed. The parts after the workflows are ignored.
+pub fn analyze_workflows(input: &str) -> Result<Vec<Issue>, ParseError> {
+    let (schema, rules) = parse_workflows(&mut lines(19, input).peekable())?;
+    Ok(analyze(&rules, &schema))
 }
 
 #[aoc(day19, part2)]
-fn solve_part2(input: &Input) -> u64 {
-    let mut current = vec![("in".to_string(), PossibleParts::new())];
-    let mut finals = vec![];
-    'outer: while let Some((rule_name, mut pp)) = current.pop() {
-        let ruleset = &input.rules[&rule_name];
-        for rule in &ruleset.rules {
-            if let Some(reduced) = pp.include(rule) {
-                match &rule.action {
-                    Action::Accept => finals.push(reduced),
-                    Action::Goto(new_rule) => current.push((new_rule.clone(), reduced)),
-                    Action::Reject => {}
+fn solve_part2(input: &Input) -> Result<u64, String> {
+    let mut current = vec![("in", input.schema.everything())];
+    let mut accepted = 0u64;
+    while let Some((rule_name, pp)) = current.pop() {
+        let ruleset = &input.rules[rule_name];
+        for (i, pp) in ruleset.route(pp) {
+            match ruleset.action(i) {
+                Action::Accept => {
+                    accepted = pp
+                        .count_u64()
+                        .and_then(|n| accepted.checked_add(n))
+                        .ok_or("too many combinations to count")?
                 }
+                Action::Goto(new_rule) => current.push((new_rule, pp)),
+                Action::Reject => {}
             }
-            if let Some(reduced) = pp.exclude(rule) {
-                pp = reduced;
-            } else {
-                continue 'outer;
+        }
+    }
+    Ok(accepted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+px{a<2006:qkq,m>2090:A,rfg}
+pv{a>1716:R,A}
+lnx{m>1548:A,A}
+rfg{s<537:gd,x>2440:R,A}
+qs{s>3448:A,lnx}
+qkq{x<1416:A,crn}
+crn{x>2662:A,R}
+in{s<1351:px,qqz}
+qqz{s>2770:qs,m<1801:hdj,R}
+gd{a>3333:R,R}
+hdj{m>838:A,pv}
+
+{x=787,m=2655,a=1222,s=2876}
+{x=1679,m=44,a=2067,s=496}
+{x=2036,m=264,a=79,s=2244}
+{x=2461,m=1339,a=466,s=291}
+{x=2127,m=1623,a=2188,s=1013}";
+
+    /// Sorts a part by walking the workflows by name, which is what part 1 did before they were
+    /// compiled.
+    fn interpret(workflows: &HashMap<String, RuleSet>, part: &Part) -> bool {
+        let mut action = Action::Goto("in".to_string());
+        'outer: while let Some(name) = action.goto() {
+            let ruleset = &workflows[name];
+            for rule in &ruleset.rules {
+                let applies = rule
+                    .conditions
+                    .iter()
+                    .all(|c| c.bound.satisfies(part.attribute(c.attribute)));
+                if applies {
+                    action = rule.action.clone();
+                    continue 'outer;
+                }
             }
+            action = ruleset.default_action.clone();
         }
-        match &ruleset.default_action {
-            Action::Accept => finals.push(pp),
-            Action::Goto(new_rule) => current.push((new_rule.clone(), pp)),
-            Action::Reject => {}
+        action == Action::Accept
+    }
+
+    /// Splits every possible part up the same way as part 2, returning the boxes that end up
+    /// being accepted or rejected, along with which.
+    fn sorted_boxes(input: &Input) -> Vec<(PossibleParts, bool)> {
+        let mut sorted = vec![];
+        let mut current = vec![("in", input.schema.everything())];
+        while let Some((name, pp)) = current.pop() {
+            let ruleset = &input.rules[name];
+            for (i, pp) in ruleset.route(pp) {
+                match ruleset.action(i) {
+                    Action::Accept => sorted.push((pp, true)),
+                    Action::Reject => sorted.push((pp, false)),
+                    Action::Goto(next) => current.push((next, pp)),
+                }
+            }
         }
+        sorted
+    }
+
+    fn check_compiled(input: &str) {
+        let input = generate(input).unwrap();
+        let boxes = sorted_boxes(&input);
+        assert!(boxes.iter().any(|(_, accepted)| *accepted));
+        for (pp, accepted) in boxes {
+            let n = pp.ranges.len();
+            for corner in 0..1 << n {
+                let part = Part {
+                    values: (0..n)
+                        .map(|a| {
+                            let (min, max) = pp.ranges[a];
+                            if corner >> a & 1 == 0 {
+                                min
+                            } else {
+                                max
+                            }
+                        })
+                        .collect(),
+                };
+                assert_eq!(interpret(&input.rules, &part), accepted, "{part:?}");
+                assert_eq!(input.program.accepts(&part), accepted, "{part:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn compiled_matches_interpreter() {
+        check_compiled(EXAMPLE);
+        check_compiled(
+            "in{x<=100&m>=50:a,a==7:R,b}\n\
+             a{s>10&s<20&x==5:A,m<60:b,R}\n\
+             b{a<=3000:A,x>3000&m==4000:A,R}",
+        );
+    }
+
+    #[test]
+    fn declared_schema() {
+        let workflows = "in{width>50&height>50:A,depth<=3:big,R}\nbig{width==0:R,A}";
+        let parts = "{width=60,height=70,depth=1}\n{width=0,height=0,depth=3}\n{width=1,height=100,depth=4}";
+        let header = "attributes: width, height, depth\ndomain: 0..=100";
+        for blank in ["", "\n"] {
+            let input = generate(&format!("{header}\n{blank}{workflows}\n\n{parts}")).unwrap();
+            assert_eq!(input.schema.attributes, ["width", "height", "depth"]);
+            assert_eq!(input.schema.domain, (0, 100));
+            assert_eq!(solve_part1(&input), 131);
+            // Big parts are accepted straight away: 50 * 50 * 101 of them. Of the rest, those with
+            // depth <= 3 are accepted unless their width is 0: (101 * 101 - 50 * 50 - 101) * 4.
+            assert_eq!(solve_part2(&input), Ok(252500 + 30400));
+        }
+        let err = generate(&format!(
+            "{header}\n{workflows}\n\n{{width=1,height=101,depth=0}}"
+        ))
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "day 19, line 6, column 10: value out of range: \"height=101\""
+        );
+    }
+
+    fn issues(workflows: &str) -> Vec<Issue> {
+        analyze_workflows(workflows).unwrap()
+    }
+
+    #[test]
+    fn missing_start() {
+        assert_eq!(issues("a{x>10:A,R}"), [Issue::MissingStart]);
+    }
+
+    #[test]
+    fn missing_target() {
+        assert_eq!(
+            issues("in{x>10:b,R}"),
+            [Issue::MissingTarget {
+                workflow: "in".into(),
+                target: "b".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn unreachable() {
+        assert_eq!(
+            issues("in{x>10:A,R}\nb{x>5:A,R}"),
+            [Issue::Unreachable {
+                workflow: "b".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn dead_rule() {
+        // Every part with x > 20 has already been accepted by the first rule.
+        assert_eq!(
+            issues("in{x>10:A,x>20:R,R}"),
+            [Issue::DeadRule {
+                workflow: "in".into(),
+                index: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn cycle() {
+        let found = issues("in{x>10:a,R}\na{m<5:in,A}");
+        assert_eq!(
+            found,
+            [Issue::Cycle {
+                workflows: vec!["a".into(), "in".into()],
+            }]
+        );
+        assert_eq!(
+            found[0].to_string(),
+            "parts can loop forever through a -> in"
+        );
+    }
+
+    #[test]
+    fn fatal_issues_stop_parsing() {
+        let err = generate("in{x>10:b,R}\n\n{x=1,m=2,a=3,s=4}").err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "day 19: workflow in sends parts to b, which doesn't exist"
+        );
+        assert!(generate("in{x>10:A,R}\nb{x>5:A,R}\n\n{x=1,m=2,a=3,s=4}").is_ok());
     }
-    finals.iter().map(|pp| pp.count()).sum::<u64>()
 }
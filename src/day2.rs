@@ -1,3 +1,4 @@
+use crate::{lines, ParseError};
 use aoc_runner_derive::{aoc, aoc_generator};
 
 #[derive(Default, Debug)]
@@ -14,34 +15,38 @@ struct Game {
 }
 
 #[aoc_generator(day2)]
-fn generate(input: &str) -> Vec<Game> {
-    input
-        .lines()
+fn generate(input: &str) -> Result<Vec<Game>, ParseError> {
+    lines(2, input)
         .map(|line| {
             let mut game = Game::default();
-            let line = line.split(": ").collect::<Vec<_>>();
-            game.id = line[0].split(" ").last().unwrap().parse().unwrap();
-            for draw in line[1].split("; ") {
+            let (header, draws) = line.split_once(": ")?;
+            let id = header
+                .strip_prefix("Game ")
+                .ok_or_else(|| line.error(header, "expected \"Game <id>\""))?;
+            game.id = line.parse(id)?;
+            for draw in draws.split("; ") {
                 let mut d = Draw::default();
                 for c in draw.split(", ") {
-                    let cube = c.split(" ").collect::<Vec<_>>();
-                    let count: u32 = cube[0].parse().unwrap();
-                    match cube[1] {
+                    let (count, color) = c
+                        .split_once(' ')
+                        .ok_or_else(|| line.error(c, "expected \"<count> <color>\""))?;
+                    let count: u32 = line.parse(count)?;
+                    match color {
                         "red" => d.r = count,
                         "green" => d.g = count,
                         "blue" => d.b = count,
-                        _ => (),
+                        _ => return Err(line.error(color, "invalid color")),
                     }
                 }
                 game.draws.push(d);
             }
-            game
+            Ok(game)
         })
         .collect()
 }
 
 #[aoc(day2, part1)]
-fn solve_part1(input: &Vec<Game>) -> u32 {
+fn solve_part1(input: &[Game]) -> u32 {
     input
         .iter()
         .filter_map(|game| {
@@ -61,7 +66,7 @@ fn solve_part1(input: &Vec<Game>) -> u32 {
 }
 
 #[aoc(day2, part2)]
-fn solve_part2(input: &Vec<Game>) -> u32 {
+fn solve_part2(input: &[Game]) -> u32 {
     input
         .iter()
         .map(|game| {
@@ -1,26 +1,23 @@
-use crate::gcd;
+use crate::{crt, lines, simulate, Line, ParseError, StateCycle};
 use aoc_runner_derive::{aoc, aoc_generator};
 use std::collections::HashMap;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::{Display, Write as _};
+use std::io::{self, Write};
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
-enum Signal {
+pub enum Signal {
     High,
     Low,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
 enum State {
     On,
+    #[default]
     Off,
 }
 
-impl Default for State {
-    fn default() -> Self {
-        State::Off
-    }
-}
-
 impl State {
     fn flip(&mut self) {
         *self = match self {
@@ -37,160 +34,526 @@ impl State {
     }
 }
 
+/// What a conjunction remembers about the last pulse it got from each of its inputs.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+struct Memory {
+    /// One bit for each input, in the order of `Module::inputs`, which is set if the last pulse
+    /// from that input was high.
+    bits: Vec<u64>,
+    /// The number of bits that are set.
+    highs: usize,
+    inputs: usize,
+}
+
+impl Memory {
+    fn new(inputs: usize) -> Self {
+        Memory {
+            bits: vec![0; inputs.div_ceil(64)],
+            highs: 0,
+            inputs,
+        }
+    }
+
+    fn get(&self, input: usize) -> bool {
+        self.bits[input / 64] >> (input % 64) & 1 == 1
+    }
+
+    fn set(&mut self, input: usize, high: bool) {
+        if self.get(input) != high {
+            self.bits[input / 64] ^= 1 << (input % 64);
+            if high {
+                self.highs += 1;
+            } else {
+                self.highs -= 1;
+            }
+        }
+    }
+
+    fn all_high(&self) -> bool {
+        self.highs == self.inputs
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 enum Kind {
+    Button,
     Broadcaster,
     FlipFlop(State),
-    Conjunction(HashMap<String, Signal>),
+    Conjunction(Memory),
+    /// A module that only appears as an output, like rx.
+    Sink,
 }
 
+/// Modules are numbered in the order their names first appear in the input, after the button.
+type Id = u16;
+
+const BUTTON: Id = 0;
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 struct Module {
-    name: String,
     kind: Kind,
-    outputs: Vec<String>,
+    /// The modules this one sends pulses to, along with its index among each of their `inputs`.
+    outputs: Vec<(Id, usize)>,
+    inputs: Vec<Id>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+struct Circuit {
+    /// The name of each module, indexed by ID.
+    names: Vec<String>,
+    ids: HashMap<String, Id>,
+    /// Indexed by ID.
+    modules: Vec<Module>,
+    /// The modules that are defined in the input, in the order they were defined.
+    defined: Vec<Id>,
+    /// How many times the button was pressed to get the modules into their current state.
+    presses: usize,
+}
+
+impl Circuit {
+    /// Returns the ID of the named module, adding it as a sink if it's new. Returns `None` if we
+    /// have run out of IDs.
+    fn intern(&mut self, name: &str) -> Option<Id> {
+        if let Some(id) = self.ids.get(name) {
+            return Some(*id);
+        }
+        let id = Id::try_from(self.modules.len()).ok()?;
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        self.modules.push(Module {
+            kind: Kind::Sink,
+            outputs: vec![],
+            inputs: vec![],
+        });
+        Some(id)
+    }
+
+    fn id(&self, name: &str) -> Option<Id> {
+        self.ids.get(name).copied()
+    }
+
+    fn name(&self, id: Id) -> &str {
+        &self.names[id as usize]
+    }
+
+    fn module(&self, id: Id) -> &Module {
+        &self.modules[id as usize]
+    }
+
+    fn broadcaster(&self) -> Id {
+        self.module(BUTTON).outputs[0].0
+    }
 }
 
 #[aoc_generator(day20)]
-fn generate(input: &str) -> HashMap<String, Module> {
-    let mut result = input
-        .lines()
-        .map(|line| {
-            let mut splits = line.split(" -> ");
-            let name_kind = splits.next().unwrap();
-            let connections = splits.next().unwrap();
-            let kind = match name_kind.chars().next().unwrap() {
-                '&' => Kind::Conjunction(Default::default()),
-                '%' => Kind::FlipFlop(Default::default()),
-                'b' => Kind::Broadcaster,
-                other => panic!("unexpected module type: {other}"),
-            };
+fn generate(input: &str) -> Result<Circuit, ParseError> {
+    let mut circuit = Circuit::default();
+    circuit.intern("button");
+    circuit.modules[BUTTON as usize].kind = Kind::Button;
+    let mut lines = lines(20, input);
+    for line in lines.by_ref() {
+        if line.text.is_empty() {
+            // The rest is the state section of a snapshot.
+            break;
+        }
+        let (name_kind, connections) = line.split_once(" -> ")?;
+        let (kind, name) = match name_kind.chars().next() {
+            Some('&') => (Kind::Conjunction(Default::default()), &name_kind[1..]),
+            Some('%') => (Kind::FlipFlop(Default::default()), &name_kind[1..]),
+            Some(_) if name_kind == "broadcaster" => (Kind::Broadcaster, name_kind),
+            _ => return Err(line.error(name_kind, "unexpected module type")),
+        };
+        if name.is_empty() {
+            return Err(line.error(name_kind, "missing module name"));
+        }
+        let mut intern = |name: &str| {
+            circuit
+                .intern(name)
+                .ok_or_else(|| line.error(name, "too many modules"))
+        };
+        let id = intern(name)?;
+        let outputs = connections
+            .split(", ")
+            .map(|o| Ok((intern(o)?, 0)))
+            .collect::<Result<Vec<_>, ParseError>>()?;
+        let module = &mut circuit.modules[id as usize];
+        match module.kind {
+            Kind::Button => return Err(line.error(name, "the button can't be redefined")),
+            Kind::Sink => {}
+            _ => return Err(line.error(name, "module is defined more than once")),
+        }
+        module.kind = kind;
+        module.outputs = outputs;
+        circuit.defined.push(id);
+    }
+    let broadcaster = circuit
+        .id("broadcaster")
+        .filter(|b| circuit.module(*b).kind == Kind::Broadcaster)
+        .ok_or_else(|| ParseError::input(20, "no broadcaster module"))?;
+    circuit.modules[BUTTON as usize].outputs = vec![(broadcaster, 0)];
 
-            let name = if kind == Kind::Broadcaster {
-                "broadcaster".to_string()
-            } else {
-                name_kind.chars().skip(1).collect()
+    // Now that every module has an ID, fill in the inputs.
+    let connections = circuit
+        .modules
+        .iter()
+        .enumerate()
+        .flat_map(|(from, m)| {
+            m.outputs
+                .iter()
+                .enumerate()
+                .map(move |(k, (to, _))| (from, k, *to))
+        })
+        .collect::<Vec<_>>();
+    for (from, k, to) in connections {
+        let inputs = &mut circuit.modules[to as usize].inputs;
+        let index = inputs.len();
+        inputs.push(from as Id);
+        circuit.modules[from].outputs[k].1 = index;
+    }
+    for module in &mut circuit.modules {
+        if let Kind::Conjunction(memory) = &mut module.kind {
+            *memory = Memory::new(module.inputs.len());
+        }
+    }
+    parse_state(&mut circuit, lines)?;
+    Ok(circuit)
+}
+
+/// Parses the state section of a snapshot, as written by `Circuit`'s `Display` impl, into
+/// `circuit`. Modules that aren't mentioned are left as they are.
+fn parse_state<'a>(
+    circuit: &mut Circuit,
+    mut lines: impl Iterator<Item = Line<'a>>,
+) -> Result<(), ParseError> {
+    let Some(header) = lines.next() else {
+        return Ok(());
+    };
+    let presses = header
+        .text
+        .strip_prefix("state after ")
+        .and_then(|s| s.strip_suffix(" presses"))
+        .ok_or_else(|| header.error(header.text, "expected \"state after N presses\""))?;
+    circuit.presses = header.parse(presses)?;
+
+    let Circuit { ids, modules, .. } = circuit;
+    for line in lines {
+        let (name_kind, state) = line.split_once(" ")?;
+        let module = ids
+            .get(name_kind.get(1..).unwrap_or(""))
+            .map(|id| &mut modules[*id as usize])
+            .ok_or_else(|| line.error(name_kind, "unknown module"))?;
+        match (name_kind.chars().next(), &mut module.kind) {
+            (Some('%'), Kind::FlipFlop(s)) => {
+                *s = match state {
+                    "on" => State::On,
+                    "off" => State::Off,
+                    _ => return Err(line.error(state, "expected \"on\" or \"off\"")),
+                }
+            }
+            (Some('&'), Kind::Conjunction(memory)) => {
+                // The same module can be an input more than once, so each mention of it is
+                // matched up with the next of its places among the inputs.
+                let mut seen = vec![false; module.inputs.len()];
+                for item in state.split(", ") {
+                    let (input, signal) = item
+                        .split_once('=')
+                        .ok_or_else(|| line.error(item, "expected \"input=signal\""))?;
+                    let high = match signal {
+                        "high" => true,
+                        "low" => false,
+                        _ => return Err(line.error(signal, "expected \"high\" or \"low\"")),
+                    };
+                    let i = (0..module.inputs.len())
+                        .find(|i| !seen[*i] && ids.get(input) == Some(&module.inputs[*i]))
+                        .ok_or_else(|| line.error(input, "not an input of this conjunction"))?;
+                    seen[i] = true;
+                    memory.set(i, high);
+                }
+            }
+            _ => return Err(line.error(name_kind, "not a flip-flop or conjunction")),
+        }
+    }
+    Ok(())
+}
+
+/// Writes the circuit in the same format as the input, followed by a blank line and a section
+/// with the state of every flip-flop and conjunction, which `generate` can read back in.
+impl Display for Circuit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let modules = self
+            .defined
+            .iter()
+            .map(|id| (self.name(*id), self.module(*id)));
+        for (name, module) in modules.clone() {
+            let prefix = match module.kind {
+                Kind::Button | Kind::Sink => unreachable!(),
+                Kind::Broadcaster => "",
+                Kind::FlipFlop(_) => "%",
+                Kind::Conjunction(_) => "&",
             };
-            let outputs = connections
-                .split(", ")
-                .map(|s| s.to_string())
+            let outputs = module
+                .outputs
+                .iter()
+                .map(|(o, _)| self.name(*o))
                 .collect::<Vec<_>>();
-            (
-                name.clone(),
-                Module {
-                    name,
-                    kind,
-                    outputs,
-                },
-            )
-        })
-        .collect::<HashMap<_, _>>();
+            writeln!(f, "{prefix}{name} -> {}", outputs.join(", "))?;
+        }
+        writeln!(f)?;
+        writeln!(f, "state after {} presses", self.presses)?;
+        for (name, module) in modules {
+            match &module.kind {
+                Kind::FlipFlop(state) => {
+                    let state = if *state == State::On { "on" } else { "off" };
+                    writeln!(f, "%{name} {state}")?;
+                }
+                Kind::Conjunction(memory) if memory.inputs > 0 => {
+                    let inputs = module
+                        .inputs
+                        .iter()
+                        .enumerate()
+                        .map(|(i, from)| {
+                            let signal = if memory.get(i) { "high" } else { "low" };
+                            format!("{}={signal}", self.name(*from))
+                        })
+                        .collect::<Vec<_>>();
+                    writeln!(f, "&{name} {}", inputs.join(", "))?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
 
-    let mut conjunction_inputs = result
-        .values()
-        .filter_map(|m| {
-            if let Kind::Conjunction(_) = m.kind {
-                Some((m.name.clone(), vec![]))
-            } else {
-                None
+/// Receives every pulse sent while simulating a circuit.
+pub trait Trace {
+    /// Called once before the first button press with the names of all the modules, including
+    /// the button.
+    fn begin(&mut self, _modules: &[&str]) {}
+
+    /// Called for each pulse, in the order they are processed. Button presses are numbered from 1.
+    fn pulse(&mut self, from: &str, to: &str, signal: Signal, press: usize);
+}
+
+impl Trace for () {
+    fn pulse(&mut self, _from: &str, _to: &str, _signal: Signal, _press: usize) {}
+}
+
+fn json_string(s: &str) -> String {
+    let mut result = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                result.push('\\');
+                result.push(c);
             }
-        })
-        .collect::<HashMap<String, Vec<String>>>();
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Writes each pulse as a line of JSON.
+pub struct JsonLines<W: Write> {
+    writer: W,
+    /// The first error we ran into, after which nothing more is written.
+    error: Option<io::Error>,
+}
+
+impl<W: Write> JsonLines<W> {
+    pub fn new(writer: W) -> Self {
+        JsonLines {
+            writer,
+            error: None,
+        }
+    }
+
+    /// Returns the writer, or the first error that happened while writing to it.
+    pub fn finish(self) -> io::Result<W> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.writer),
+        }
+    }
+}
 
-    for (name, module) in &mut result {
-        for output in &module.outputs {
-            if let Some(v) = conjunction_inputs.get_mut(output) {
-                v.push(name.clone());
+impl<W: Write> Trace for JsonLines<W> {
+    fn pulse(&mut self, from: &str, to: &str, signal: Signal, press: usize) {
+        if self.error.is_some() {
+            return;
+        }
+        let signal = match signal {
+            Signal::High => "high",
+            Signal::Low => "low",
+        };
+        let result = writeln!(
+            self.writer,
+            "{{\"press\":{press},\"from\":{},\"to\":{},\"signal\":\"{signal}\"}}",
+            json_string(from),
+            json_string(to)
+        );
+        self.error = result.err();
+    }
+}
+
+/// Writes a VCD waveform with one wire per module, holding the last signal the module sent, and
+/// a counter of button presses. Each pulse takes one unit of time.
+pub struct Vcd<W: Write> {
+    writer: W,
+    /// VCD identifiers of each module's wire.
+    ids: HashMap<String, String>,
+    time: usize,
+    press: usize,
+    /// The first error we ran into, after which nothing more is written.
+    error: Option<io::Error>,
+}
+
+impl<W: Write> Vcd<W> {
+    /// The identifier of the button press counter.
+    const PRESS_ID: &'static str = "!";
+
+    pub fn new(writer: W) -> Self {
+        Vcd {
+            writer,
+            ids: HashMap::new(),
+            time: 0,
+            press: 0,
+            error: None,
+        }
+    }
+
+    /// Returns the writer, or the first error that happened while writing to it.
+    pub fn finish(self) -> io::Result<W> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.writer),
+        }
+    }
+
+    /// Returns the `i`th identifier after the press counter's. Identifiers are made up of the
+    /// printable ASCII characters.
+    fn id(mut i: usize) -> String {
+        let mut id = String::new();
+        i += 1;
+        loop {
+            id.push((b'!' + (i % 94) as u8) as char);
+            i /= 94;
+            if i == 0 {
+                return id;
             }
         }
     }
 
-    for (name, inputs) in conjunction_inputs {
-        if let Some(Module {
-            kind: Kind::Conjunction(h),
-            ..
-        }) = result.get_mut(&name)
-        {
-            h.extend(inputs.iter().map(|s| (s.clone(), Signal::Low)));
+    fn write_header(&mut self, modules: &[&str]) -> io::Result<()> {
+        let w = &mut self.writer;
+        writeln!(w, "$timescale 1ns $end")?;
+        writeln!(w, "$scope module circuit $end")?;
+        writeln!(w, "$var integer 32 {} press $end", Self::PRESS_ID)?;
+        for (i, name) in modules.iter().enumerate() {
+            let id = Self::id(i);
+            writeln!(w, "$var wire 1 {id} {name} $end")?;
+            self.ids.insert(name.to_string(), id);
+        }
+        writeln!(w, "$upscope $end")?;
+        writeln!(w, "$enddefinitions $end")?;
+        writeln!(w, "#0")?;
+        writeln!(w, "$dumpvars")?;
+        writeln!(w, "b0 {}", Self::PRESS_ID)?;
+        for i in 0..modules.len() {
+            writeln!(w, "0{}", Self::id(i))?;
         }
+        writeln!(w, "$end")
     }
 
-    result
+    fn write_pulse(&mut self, from: &str, signal: Signal, press: usize) -> io::Result<()> {
+        self.time += 1;
+        writeln!(self.writer, "#{}", self.time)?;
+        if press != self.press {
+            self.press = press;
+            writeln!(self.writer, "b{press:b} {}", Self::PRESS_ID)?;
+        }
+        if let Some(id) = self.ids.get(from) {
+            let value = match signal {
+                Signal::High => 1,
+                Signal::Low => 0,
+            };
+            writeln!(self.writer, "{value}{id}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Trace for Vcd<W> {
+    fn begin(&mut self, modules: &[&str]) {
+        if self.error.is_none() {
+            self.error = self.write_header(modules).err();
+        }
+    }
+
+    fn pulse(&mut self, from: &str, _to: &str, signal: Signal, press: usize) {
+        if self.error.is_none() {
+            self.error = self.write_pulse(from, signal, press).err();
+        }
+    }
 }
 
-// Run one button press and return the number of low and high signals that were observed, respectively.
-fn run(system: &mut HashMap<String, Module>, to_track: &mut [(String, bool)]) -> (usize, usize) {
-    let mut to_process = VecDeque::new();
+// Run one button press, calling `pulse` with the sender, receiver and signal of each pulse in the
+// order they are processed, and return the number of low and high signals that were observed,
+// respectively.
+fn run(modules: &mut [Module], mut pulse: impl FnMut(Id, Id, Signal)) -> (usize, usize) {
+    let mut to_process = modules[BUTTON as usize]
+        .outputs
+        .iter()
+        .map(|&(to, input)| (BUTTON, to, input, Signal::Low))
+        .collect::<VecDeque<_>>();
     let mut highs = 0;
-    let mut lows = 1;
-    let bcast_outputs = &system["broadcaster"].outputs;
-    lows += bcast_outputs.len();
-    to_process.extend(
-        bcast_outputs
-            .iter()
-            .map(|o| (o.clone(), "broadcaster".to_string(), Signal::Low)),
-    );
-    while let Some((name, from, signal)) = to_process.pop_front() {
-        if signal == Signal::Low {
-            for (tracked_name, seen) in to_track.iter_mut() {
-                if name == *tracked_name {
-                    *seen = true;
-                }
-            }
+    let mut lows = 0;
+    while let Some((from, to, input, signal)) = to_process.pop_front() {
+        pulse(from, to, signal);
+        match signal {
+            Signal::High => highs += 1,
+            Signal::Low => lows += 1,
         }
-        if let Some(module) = system.get_mut(&name) {
-            match &mut module.kind {
-                Kind::FlipFlop(ref mut state) => {
-                    if signal == Signal::Low {
-                        state.flip();
-                        to_process.extend(
-                            module
-                                .outputs
-                                .iter()
-                                .map(|o| (o.clone(), name.clone(), state.signal())),
-                        );
-                        if state.signal() == Signal::High {
-                            highs += module.outputs.len();
-                        } else {
-                            lows += module.outputs.len();
-                        }
-                    }
+        let module = &mut modules[to as usize];
+        let signal = match &mut module.kind {
+            Kind::Broadcaster => signal,
+            Kind::FlipFlop(state) => {
+                if signal == Signal::High {
+                    continue;
                 }
-                Kind::Conjunction(h) => {
-                    *h.get_mut(&from).unwrap() = signal;
-                    if h.values().any(|s| s == &Signal::Low) {
-                        to_process.extend(
-                            module
-                                .outputs
-                                .iter()
-                                .map(|o| (o.clone(), name.clone(), Signal::High)),
-                        );
-                        highs += module.outputs.len();
-                    } else {
-                        to_process.extend(
-                            module
-                                .outputs
-                                .iter()
-                                .map(|o| (o.clone(), name.clone(), Signal::Low)),
-                        );
-                        lows += module.outputs.len();
-                    }
+                state.flip();
+                state.signal()
+            }
+            Kind::Conjunction(memory) => {
+                memory.set(input, signal == Signal::High);
+                if memory.all_high() {
+                    Signal::Low
+                } else {
+                    Signal::High
                 }
-                _ => panic!(),
             }
-        }
+            Kind::Button | Kind::Sink => continue,
+        };
+        to_process.extend(
+            module
+                .outputs
+                .iter()
+                .map(|&(output, input)| (to, output, input, signal)),
+        );
     }
     (lows, highs)
 }
 
 #[aoc(day20, part1)]
-fn solve_part1(input: &HashMap<String, Module>) -> usize {
-    let mut system = input.clone();
+fn solve_part1(input: &Circuit) -> usize {
+    let mut modules = input.modules.clone();
 
     let mut lows = 0;
     let mut highs = 0;
-    for _ in 0..1000 {
-        let (dl, dh) = run(&mut system, &mut []);
+    for _ in 1..=1000 {
+        let (dl, dh) = run(&mut modules, |_, _, _| {});
         lows += dl;
         highs += dh;
     }
@@ -198,45 +561,658 @@ fn solve_part1(input: &HashMap<String, Module>) -> usize {
 }
 
 #[aoc(day20, part2)]
-fn solve_part2(input: &HashMap<String, Module>) -> usize {
-    let mut system = input.clone();
-
-    let collector = input
-        .values()
-        .find_map(|m| {
-            if m.outputs.contains(&"rx".to_string()) {
-                Some(m.name.clone())
-            } else {
-                None
+fn solve_part2(input: &Circuit) -> Result<usize, String> {
+    first_low_to_rx(input)
+}
+
+/// Parses a circuit and presses the button `presses` times, sending every pulse to `trace`. If the
+/// input is a snapshot, the presses are numbered from where it left off.
+pub fn trace<T: Trace>(input: &str, presses: usize, trace: &mut T) -> Result<(), ParseError> {
+    let mut circuit = generate(input)?;
+    // Sinks never send anything, so they're left out.
+    let mut modules = circuit
+        .names
+        .iter()
+        .zip(&circuit.modules)
+        .filter(|(_, m)| m.kind != Kind::Sink)
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    modules.sort();
+    trace.begin(&modules);
+    let Circuit {
+        names,
+        modules,
+        presses: done,
+        ..
+    } = &mut circuit;
+    for press in *done + 1..=*done + presses {
+        run(modules, |from, to, signal| {
+            trace.pulse(&names[from as usize], &names[to as usize], signal, press)
+        });
+    }
+    Ok(())
+}
+
+/// Returns `from` and every module that it sends pulses to, directly or indirectly.
+fn reachable(circuit: &Circuit, from: Id) -> BTreeSet<Id> {
+    let mut seen = BTreeSet::from([from]);
+    let mut stack = vec![from];
+    while let Some(id) = stack.pop() {
+        for &(output, _) in &circuit.module(id).outputs {
+            if seen.insert(output) {
+                stack.push(output);
             }
+        }
+    }
+    seen
+}
+
+/// Splits the circuit into the parts fed by each of the broadcaster's outputs. Each part is the
+/// modules that only that output can reach, so anything where the parts come back together is
+/// left out. Returns the parts along with the output that feeds them.
+fn subcircuits(circuit: &Circuit) -> Vec<(Id, BTreeSet<Id>)> {
+    let reached = circuit
+        .module(circuit.broadcaster())
+        .outputs
+        .iter()
+        .map(|&(o, _)| (o, reachable(circuit, o)))
+        .collect::<Vec<_>>();
+    reached
+        .iter()
+        .enumerate()
+        .map(|(i, (entry, modules))| {
+            let only_here = modules
+                .iter()
+                .filter(|m| {
+                    reached
+                        .iter()
+                        .enumerate()
+                        .all(|(j, (_, others))| i == j || !others.contains(*m))
+                })
+                .copied()
+                .collect();
+            (*entry, only_here)
         })
-        .unwrap();
-    let mut feeders = input
-        .values()
-        .filter_map(|m| {
-            if m.outputs.contains(&collector) {
-                Some((m.name.clone(), false))
-            } else {
-                None
+        .collect()
+}
+
+/// Returns the state of the given modules as a list of bits: the state of each flip-flop and the
+/// memory of each conjunction, with its inputs in order.
+fn state_bits(modules: &[Module], ids: &[Id]) -> Vec<bool> {
+    let mut bits = vec![];
+    for id in ids {
+        match &modules[*id as usize].kind {
+            Kind::FlipFlop(state) => bits.push(*state == State::On),
+            Kind::Conjunction(memory) => bits.extend((0..memory.inputs).map(|i| memory.get(i))),
+            Kind::Button | Kind::Broadcaster | Kind::Sink => {}
+        }
+    }
+    bits
+}
+
+/// The opposite of `state_bits`. Returns the bits that weren't needed.
+fn restore_bits<'a>(modules: &mut [Module], ids: &[Id], mut bits: &'a [bool]) -> &'a [bool] {
+    for id in ids {
+        match &mut modules[*id as usize].kind {
+            Kind::FlipFlop(state) => {
+                *state = if bits[0] { State::On } else { State::Off };
+                bits = &bits[1..];
             }
+            Kind::Conjunction(memory) => {
+                for (i, high) in bits[..memory.inputs].iter().enumerate() {
+                    memory.set(i, *high);
+                }
+                bits = &bits[memory.inputs..];
+            }
+            Kind::Button | Kind::Broadcaster | Kind::Sink => {}
+        }
+    }
+    bits
+}
+
+/// How one of the independent parts of a circuit behaves over a cycle of button presses.
+struct Cycle {
+    /// The modules in this part.
+    modules: Vec<Id>,
+    /// The modules in this part that feed the final conjunction, along with their index among its
+    /// inputs.
+    outputs: Vec<(Id, usize)>,
+    /// The `state_bits` of `modules` before each press, followed by the final conjunction's
+    /// memory of `outputs`.
+    states: StateCycle<Vec<bool>>,
+    /// For each of the states in `states`, whether the final conjunction remembers a high pulse
+    /// from every one of `outputs` at some point during the press that follows it.
+    all_high: Vec<bool>,
+}
+
+impl Cycle {
+    /// The most presses we will simulate while looking for a cycle.
+    const MAX_PRESSES: u64 = 1 << 18;
+
+    /// Presses the button on its own copy of one part of the circuit until it gets back to a state
+    /// it has been in before. `conjunction` is where the parts come back together.
+    fn find(
+        input: &Circuit,
+        entry: Id,
+        modules: &BTreeSet<Id>,
+        conjunction: Id,
+    ) -> Result<Self, String> {
+        let outputs = input
+            .module(conjunction)
+            .inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| modules.contains(m))
+            .map(|(i, m)| (*m, i))
+            .collect::<Vec<_>>();
+        let modules = modules.iter().copied().collect::<Vec<_>>();
+        // Only this part gets pulses from the broadcaster. Apart from it, the only modules that
+        // get pulses are the conjunction and the sinks it feeds, which can't affect the part.
+        let mut system = input.modules.clone();
+        system[input.broadcaster() as usize]
+            .outputs
+            .retain(|(o, _)| *o == entry);
+
+        let mut start = state_bits(&system, &modules);
+        start.extend(vec![false; outputs.len()]);
+        let mut all_high = vec![];
+        let states = simulate(start, Self::MAX_PRESSES, |state| {
+            let mut memory = restore_bits(&mut system, &modules, state).to_vec();
+            let mut high = memory.iter().all(|h| *h);
+            run(&mut system, |from, to, signal| {
+                if to != conjunction {
+                    return;
+                }
+                if let Some(i) = outputs.iter().position(|(o, _)| *o == from) {
+                    memory[i] = signal == Signal::High;
+                    high |= memory.iter().all(|h| *h);
+                }
+            });
+            all_high.push(high);
+            let mut next = state_bits(&system, &modules);
+            next.extend(memory);
+            next
+        })
+        .map_err(|_| {
+            format!(
+                "the part fed by {} doesn't cycle within {} presses",
+                input.name(entry),
+                Self::MAX_PRESSES
+            )
+        })?;
+        Ok(Cycle {
+            modules,
+            outputs,
+            states,
+            all_high,
+        })
+    }
+
+    /// Returns the index into `states` and `all_high` for the given press.
+    fn index(&self, press: usize) -> usize {
+        self.states.index(press as u64 - 1)
+    }
+}
+
+/// The parts of a circuit fed by each of the broadcaster's outputs, along with those outputs.
+type Parts = Vec<(Id, BTreeSet<Id>)>;
+
+/// Checks that `rx` is fed by a single conjunction, and that everything else is part of one of
+/// the `subcircuits`, each of which only connects to the rest of the circuit through that
+/// conjunction. Returns the conjunction and the modules in each part.
+fn check_decomposable(circuit: &Circuit) -> Result<(Id, Parts), String> {
+    let feeders = circuit
+        .id("rx")
+        .map_or(&[][..], |rx| &circuit.module(rx).inputs[..]);
+    let conjunction = match feeders {
+        [m] if matches!(circuit.module(*m).kind, Kind::Conjunction(_)) => *m,
+        [_] => return Err("rx is not fed by a conjunction".into()),
+        _ => return Err(format!("rx has {} inputs instead of one", feeders.len())),
+    };
+    let is_sink = |m: Id| circuit.module(m).kind == Kind::Sink;
+    // If there's only one part, the conjunction and rx will be in it, but they're where we
+    // combine the parts rather than part of any of them. Sinks don't have any state, so we can
+    // leave them out too.
+    let mut parts = subcircuits(circuit);
+    for (_, modules) in &mut parts {
+        modules.retain(|m| *m != conjunction && !is_sink(*m));
+    }
+    // Modules that the broadcaster can't reach never get a pulse, so they never send one either,
+    // and they aren't part of any subcircuit.
+    let live = reachable(circuit, circuit.broadcaster());
+    for (id, module) in circuit.modules.iter().enumerate() {
+        let id = id as Id;
+        if id == conjunction
+            || !live.contains(&id)
+            || matches!(module.kind, Kind::Button | Kind::Broadcaster | Kind::Sink)
+        {
+            continue;
+        }
+        let Some((_, modules)) = parts.iter().find(|(_, modules)| modules.contains(&id)) else {
+            return Err(format!(
+                "{} is shared between subcircuits",
+                circuit.name(id)
+            ));
+        };
+        if let Some((o, _)) = module
+            .outputs
+            .iter()
+            .find(|(o, _)| *o != conjunction && !is_sink(*o) && !modules.contains(o))
+        {
+            return Err(format!(
+                "{} connects its subcircuit to {}",
+                circuit.name(id),
+                circuit.name(*o)
+            ));
+        }
+    }
+    if let Some((o, _)) = circuit
+        .module(conjunction)
+        .outputs
+        .iter()
+        .find(|(o, _)| !is_sink(*o))
+    {
+        return Err(format!(
+            "{} feeds back into {}",
+            circuit.name(conjunction),
+            circuit.name(*o)
+        ));
+    }
+    Ok((conjunction, parts))
+}
+
+/// Finds the first button press that sends a low pulse to `rx`.
+///
+/// The circuit has to be made up of independent parts, fed by each of the broadcaster's outputs,
+/// which only come together in the conjunction feeding `rx`. Each part eventually cycles through
+/// the same states, so it can only leave the conjunction remembering high pulses from it on
+/// presses that fall into certain residue classes, which we combine with the Chinese remainder
+/// theorem. Since the timing of pulses within a press matters too, each candidate press is
+/// checked by putting every part into the state it would be in and simulating the whole circuit.
+fn first_low_to_rx(input: &Circuit) -> Result<usize, String> {
+    let (conjunction, parts) = check_decomposable(input)?;
+    let rx = input.ids["rx"];
+    // Parts that don't feed the conjunction can't make a difference.
+    let feeds = |m: &Id| {
+        input
+            .module(*m)
+            .outputs
+            .iter()
+            .any(|(o, _)| *o == conjunction)
+    };
+    let cycles = parts
+        .into_iter()
+        .filter(|(_, modules)| modules.iter().any(feeds))
+        .map(|(entry, modules)| Cycle::find(input, entry, &modules, conjunction))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let sends_low = |press: usize| {
+        let mut system = input.modules.clone();
+        for cycle in &cycles {
+            let state = &cycle.states.history[cycle.index(press)];
+            let memory = restore_bits(&mut system, &cycle.modules, state);
+            let Kind::Conjunction(m) = &mut system[conjunction as usize].kind else {
+                unreachable!();
+            };
+            for ((_, i), high) in cycle.outputs.iter().zip(memory) {
+                m.set(*i, *high);
+            }
+        }
+        let mut low = false;
+        run(&mut system, |_, to, signal| {
+            low |= to == rx && signal == Signal::Low;
+        });
+        low
+    };
+    let possible = |press: usize| cycles.iter().all(|c| c.all_high[c.index(press)]);
+
+    // Before every part has started cycling, we just check each press.
+    let start = cycles.iter().map(|c| c.states.offset).max().unwrap_or(0);
+    if let Some(press) = (1..=start).find(|p| possible(*p) && sends_low(*p)) {
+        return Ok(press);
+    }
+
+    // After that, each part allows some presses modulo its period.
+    let residues = cycles
+        .iter()
+        .map(|c| {
+            (c.states.offset..c.states.offset + c.states.period)
+                .filter(|i| c.all_high[*i])
+                .map(|i| ((i + 1) as i128, c.states.period as i128))
+                .collect::<Vec<_>>()
         })
         .collect::<Vec<_>>();
-    let len = feeders.len();
+    let combinations = residues.iter().map(|r| r.len()).product::<usize>();
+    if combinations > 100000 {
+        return Err(format!(
+            "too many combinations of presses to check ({combinations})"
+        ));
+    }
+    let mut candidates = vec![];
+    let mut choice = vec![0; residues.len()];
+    for _ in 0..combinations {
+        let congruences = choice
+            .iter()
+            .zip(&residues)
+            .map(|(i, r)| r[*i])
+            .collect::<Vec<_>>();
+        if let Some((x, period)) = crt(&congruences) {
+            // The smallest solution after `start`.
+            let press = x + ((start as i128 - x).div_euclid(period) + 1) * period;
+            candidates.push(press);
+        }
+        // Move on to the next combination, like counting with mixed radixes.
+        for (c, r) in choice.iter_mut().zip(&residues) {
+            *c += 1;
+            if *c < r.len() {
+                break;
+            }
+            *c = 0;
+        }
+    }
+    candidates.sort();
+    // Everything repeats with the period of the solutions, so if a candidate doesn't work, none
+    // of the later ones from the same combination will either.
+    for press in candidates {
+        let press = usize::try_from(press).map_err(|_| format!("press {press} is too large"))?;
+        if sends_low(press) {
+            return Ok(press);
+        }
+    }
+    Err("rx never gets a low pulse".into())
+}
+
+/// Renders the circuit as a Graphviz graph, optionally grouping the modules into `subcircuits`.
+fn dot(circuit: &Circuit, subgraphs: bool) -> String {
+    let shape = |id: Id| match circuit.module(id).kind {
+        Kind::Broadcaster => "house",
+        Kind::FlipFlop(_) => "box",
+        Kind::Conjunction(_) => "invtrapezium",
+        // The button is never drawn.
+        Kind::Sink | Kind::Button => "doublecircle",
+    };
+    let modules = circuit
+        .ids
+        .iter()
+        .filter(|(_, id)| **id != BUTTON)
+        .map(|(name, id)| (name.as_str(), *id))
+        .collect::<BTreeMap<_, _>>();
+    let mut names = modules.clone();
+
+    let mut out = String::from("digraph circuit {\n");
+    if subgraphs {
+        let parts = subcircuits(circuit);
+        let parts = parts.iter().filter(|(_, modules)| !modules.is_empty());
+        for (i, (entry, part)) in parts.enumerate() {
+            writeln!(out, "  subgraph cluster_{i} {{").unwrap();
+            writeln!(out, "    label=\"fed by {}\";", circuit.name(*entry)).unwrap();
+            let part = part
+                .iter()
+                .map(|id| (circuit.name(*id), *id))
+                .collect::<BTreeMap<_, _>>();
+            for (name, id) in part {
+                writeln!(out, "    \"{name}\" [shape={}];", shape(id)).unwrap();
+                names.remove(name);
+            }
+            writeln!(out, "  }}").unwrap();
+        }
+    }
+    for (name, id) in names {
+        writeln!(out, "  \"{name}\" [shape={}];", shape(id)).unwrap();
+    }
+    for (name, id) in modules {
+        for (output, _) in &circuit.module(id).outputs {
+            writeln!(out, "  \"{name}\" -> \"{}\";", circuit.name(*output)).unwrap();
+        }
+    }
+    out.push_str("}\n");
+    out
+}
 
-    let mut loop_lengths = HashMap::<String, usize>::new();
+/// Parses a circuit and renders it as a Graphviz graph. If `subgraphs` is set, the independent
+/// parts of the circuit that are fed by each of the broadcaster's outputs are drawn as clusters.
+pub fn to_dot(input: &str, subgraphs: bool) -> Result<String, ParseError> {
+    Ok(dot(&generate(input)?, subgraphs))
+}
 
-    let mut i = 0;
-    while loop_lengths.len() < len {
-        run(&mut system, &mut feeders[..]);
+/// Parses a circuit, or a snapshot of one, presses the button `presses` more times, and returns a
+/// snapshot of the result. Snapshots are accepted anywhere a circuit is.
+pub fn snapshot(input: &str, presses: usize) -> Result<String, ParseError> {
+    let mut circuit = generate(input)?;
+    for _ in 0..presses {
+        run(&mut circuit.modules, |_, _, _| {});
+    }
+    circuit.presses += presses;
+    Ok(circuit.to_string())
+}
 
-        i += 1;
-        for (name, seen) in &feeders {
-            if *seen {
-                loop_lengths.entry(name.clone()).or_insert(i);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// A flip-flop feeding an inverter, so the output goes high every other press.
+    const BLINKER: &str = "broadcaster -> a\n%a -> con\n&con -> output";
+
+    /// Builds a circuit like the puzzle's, where each of the broadcaster's outputs feeds a binary
+    /// counter that resets itself every `n` presses.
+    fn counters(periods: &[usize]) -> String {
+        let mut lines = vec![];
+        let mut entries = vec![];
+        for (p, n) in periods.iter().enumerate() {
+            let bits = usize::BITS - n.leading_zeros();
+            entries.push(format!("p{p}b0"));
+            let mut resets = vec![format!("p{p}inv")];
+            for b in 0..bits {
+                let mut outputs = vec![];
+                if b + 1 < bits {
+                    outputs.push(format!("p{p}b{}", b + 1));
+                }
+                if n >> b & 1 == 1 {
+                    outputs.push(format!("p{p}c"));
+                }
+                if b == 0 || n >> b & 1 == 0 {
+                    resets.push(format!("p{p}b{b}"));
+                }
+                lines.push(format!("%p{p}b{b} -> {}", outputs.join(", ")));
+            }
+            lines.push(format!("&p{p}c -> {}", resets.join(", ")));
+            lines.push(format!("&p{p}inv -> hub"));
+        }
+        lines.push(format!("broadcaster -> {}", entries.join(", ")));
+        lines.push("&hub -> rx".into());
+        lines.join("\n")
+    }
+
+    /// Presses the button until `rx` gets a low pulse.
+    fn brute_force(circuit: &Circuit, limit: usize) -> Option<usize> {
+        let rx = circuit.id("rx")?;
+        let mut modules = circuit.modules.clone();
+        (1..=limit).find(|_| {
+            let mut low = false;
+            run(&mut modules, |_, to, signal| {
+                low |= to == rx && signal == Signal::Low;
+            });
+            low
+        })
+    }
+
+    #[test]
+    fn first_low_to_rx_matches_brute_force() {
+        for periods in [&[11, 13, 23][..], &[9, 15, 21], &[6, 10], &[7, 7, 5], &[12]] {
+            let circuit = generate(&counters(periods)).unwrap();
+            let expected = brute_force(&circuit, 100000);
+            assert!(expected.is_some(), "{periods:?}");
+            assert_eq!(first_low_to_rx(&circuit).ok(), expected, "{periods:?}");
+        }
+    }
+
+    #[test]
+    fn unreachable_modules_are_ignored() {
+        // Nothing sends pulses to `z`, so it can't be shared between the subcircuits.
+        let input = format!("{}\n%z -> p0b0, p1b0", counters(&[3, 5]));
+        let circuit = generate(&input).unwrap();
+        assert!(check_decomposable(&circuit).is_ok());
+        assert_eq!(first_low_to_rx(&circuit), Ok(15));
+
+        let shared = format!("{}\n%z -> p0b0, p1b0", counters(&[3, 5]))
+            .replace("broadcaster -> p0b0, p1b0", "broadcaster -> p0b0, p1b0, z");
+        let circuit = generate(&shared).unwrap();
+        let error = check_decomposable(&circuit).unwrap_err();
+        assert!(error.ends_with("is shared between subcircuits"), "{error}");
+    }
+
+    #[test]
+    fn snapshots_resume() {
+        let example = "broadcaster -> a\n%a -> inv, con\n&inv -> b\n%b -> con\n&con -> output";
+        for input in [BLINKER, example, &counters(&[5, 6])] {
+            for n in 0..8 {
+                let saved = snapshot(input, n).unwrap();
+                for m in 0..8 {
+                    assert_eq!(
+                        snapshot(&saved, m),
+                        snapshot(input, n + m),
+                        "{n} + {m} presses of {input:?}"
+                    );
+                }
             }
         }
     }
 
-    let product = loop_lengths.values().product::<usize>();
-    product / loop_lengths.values().fold(product, |acc, n| gcd(acc, *n))
+    #[test]
+    fn dot_output() {
+        let input = "broadcaster -> a, b\n%a -> ca\n&ca -> hub\n%b -> cb\n&cb -> hub\n&hub -> rx";
+        let edges = "  \"a\" -> \"ca\";
+  \"b\" -> \"cb\";
+  \"broadcaster\" -> \"a\";
+  \"broadcaster\" -> \"b\";
+  \"ca\" -> \"hub\";
+  \"cb\" -> \"hub\";
+  \"hub\" -> \"rx\";
+}
+";
+        assert_eq!(
+            to_dot(input, false).unwrap(),
+            "digraph circuit {
+  \"a\" [shape=box];
+  \"b\" [shape=box];
+  \"broadcaster\" [shape=house];
+  \"ca\" [shape=invtrapezium];
+  \"cb\" [shape=invtrapezium];
+  \"hub\" [shape=invtrapezium];
+  \"rx\" [shape=doublecircle];
+"
+            .to_owned()
+                + edges
+        );
+
+        let clustered = to_dot(input, true).unwrap();
+        let parts = subcircuits(&generate(input).unwrap());
+        assert_eq!(clustered.matches("subgraph cluster_").count(), parts.len());
+        assert_eq!(
+            clustered,
+            "digraph circuit {
+  subgraph cluster_0 {
+    label=\"fed by a\";
+    \"a\" [shape=box];
+    \"ca\" [shape=invtrapezium];
+  }
+  subgraph cluster_1 {
+    label=\"fed by b\";
+    \"b\" [shape=box];
+    \"cb\" [shape=invtrapezium];
+  }
+  \"broadcaster\" [shape=house];
+  \"hub\" [shape=invtrapezium];
+  \"rx\" [shape=doublecircle];
+"
+            .to_owned()
+                + edges
+        );
+    }
+
+    #[test]
+    fn json_lines() {
+        let mut sink = JsonLines::new(vec![]);
+        trace(BLINKER, 2, &mut sink).unwrap();
+        assert_eq!(
+            String::from_utf8(sink.finish().unwrap()).unwrap(),
+            "\
+{\"press\":1,\"from\":\"button\",\"to\":\"broadcaster\",\"signal\":\"low\"}
+{\"press\":1,\"from\":\"broadcaster\",\"to\":\"a\",\"signal\":\"low\"}
+{\"press\":1,\"from\":\"a\",\"to\":\"con\",\"signal\":\"high\"}
+{\"press\":1,\"from\":\"con\",\"to\":\"output\",\"signal\":\"low\"}
+{\"press\":2,\"from\":\"button\",\"to\":\"broadcaster\",\"signal\":\"low\"}
+{\"press\":2,\"from\":\"broadcaster\",\"to\":\"a\",\"signal\":\"low\"}
+{\"press\":2,\"from\":\"a\",\"to\":\"con\",\"signal\":\"low\"}
+{\"press\":2,\"from\":\"con\",\"to\":\"output\",\"signal\":\"high\"}
+"
+        );
+    }
+
+    #[test]
+    fn vcd() {
+        let mut sink = Vcd::new(vec![]);
+        trace(BLINKER, 2, &mut sink).unwrap();
+        let out = String::from_utf8(sink.finish().unwrap()).unwrap();
+        let (header, changes) = out.split_once("$end\n#1\n").unwrap();
+        assert_eq!(
+            header,
+            "\
+$timescale 1ns $end
+$scope module circuit $end
+$var integer 32 ! press $end
+$var wire 1 \" a $end
+$var wire 1 # broadcaster $end
+$var wire 1 $ button $end
+$var wire 1 % con $end
+$upscope $end
+$enddefinitions $end
+#0
+$dumpvars
+b0 !
+0\"
+0#
+0$
+0%
+"
+        );
+        // Sinks never send anything, so `output` doesn't get a wire at all.
+        assert!(header.lines().all(|line| !line.ends_with(" output $end")));
+        // Each pulse gets its own timestamp, and the press counter changes with the first pulse
+        // of each press.
+        assert_eq!(
+            changes,
+            "\
+b1 !
+0$
+#2
+0#
+#3
+1\"
+#4
+0%
+#5
+b10 !
+0$
+#6
+0#
+#7
+0\"
+#8
+1%
+"
+        );
+    }
+
+    #[test]
+    fn vcd_ids() {
+        type V = Vcd<Vec<u8>>;
+        assert_eq!(V::id(0), "\"");
+        assert_eq!(V::id(92), "~");
+        assert_eq!(V::id(93), "!\"");
+        let ids = (0..10000).map(V::id).collect::<HashSet<_>>();
+        assert_eq!(ids.len(), 10000);
+        assert!(!ids.contains(V::PRESS_ID));
+    }
 }
@@ -1,7 +1,7 @@
-use crate::Dir;
+use crate::{Dir, Grid, ParseError, Plane, TiledPlane};
 use aoc_runner_derive::{aoc, aoc_generator};
-use array2d::Array2D;
 use std::collections::HashSet;
+use std::ops::Index;
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
 enum Location {
@@ -11,61 +11,174 @@ enum Location {
 
 #[derive(Clone, Debug)]
 struct Input {
-    map: Array2D<Location>,
+    map: Grid<Location>,
     start_location: (i64, i64),
 }
 
 #[aoc_generator(day21)]
-fn generate(input: &str) -> Input {
-    let width = input.lines().next().unwrap().len();
-    let height = input.lines().count();
-    let start_location = input
-        .lines()
-        .enumerate()
-        .map(|(y, line)| {
-            line.chars().enumerate().filter_map(move |(x, c)| match c {
-                'S' => Some((y as i64, x as i64)),
-                _ => None,
-            })
-        })
-        .flatten()
-        .next()
-        .unwrap();
-    let map = Array2D::from_iter_row_major(
-        input
-            .lines()
-            .map(|line| {
-                line.chars().map(|c| match c {
-                    '.' | 'S' => Location::GardenPlot,
-                    '#' => Location::Rock,
-                    other => panic!("invalid char {other}"),
-                })
-            })
-            .flatten(),
-        height,
-        width,
-    )
-    .unwrap();
-    Input {
-        map,
-        start_location,
-    }
+fn generate(input: &str) -> Result<Input, ParseError> {
+    let chars = Grid::parse(21, input, |c| matches!(c, '.' | 'S' | '#').then_some(c))?;
+    Ok(Input {
+        start_location: chars.find_unique(21, "start location", |c| *c == 'S')?,
+        map: chars.map(|c| match c {
+            '#' => Location::Rock,
+            _ => Location::GardenPlot,
+        }),
+    })
 }
 
-#[aoc(day21, part1)]
-fn solve_part1(input: &Input) -> i64 {
+/// Returns the number of garden plots reachable from `start` in exactly `steps` steps.
+fn reachable<P>(map: &P, start: (i64, i64), steps: usize) -> usize
+where
+    P: Plane + Index<(i64, i64), Output = Location>,
+{
     let mut current = HashSet::new();
     let mut next = HashSet::new();
-    current.insert(input.start_location);
-    for _ in 0..64 {
+    current.insert(start);
+    for _ in 0..steps {
         for pos in current.drain() {
-            for (y, x) in Dir::neighbors(pos, &input.map) {
-                if input.map[(y as usize, x as usize)] == Location::GardenPlot {
-                    next.insert((y, x));
+            for pos in Dir::neighbors(pos, map) {
+                if map[pos] == Location::GardenPlot {
+                    next.insert(pos);
                 }
             }
         }
         std::mem::swap(&mut current, &mut next);
     }
-    current.len() as i64
+    current.len()
+}
+
+#[aoc(day21, part1)]
+fn solve_part1(input: &Input) -> i64 {
+    reachable(&input.map, input.start_location, 64) as i64
+}
+
+/// Returns the distance from `start` to every garden plot that can be reached in at most
+/// `max_steps` steps.
+fn distances<P>(map: &P, start: (i64, i64), max_steps: usize) -> Vec<usize>
+where
+    P: Plane + Index<(i64, i64), Output = Location>,
+{
+    let mut seen = HashSet::new();
+    let mut result = vec![0];
+    let mut frontier = vec![start];
+    let mut next = vec![];
+    seen.insert(start);
+    for step in 1..=max_steps {
+        for pos in frontier.drain(..) {
+            for pos in Dir::neighbors(pos, map) {
+                if map[pos] == Location::GardenPlot && seen.insert(pos) {
+                    next.push(pos);
+                    result.push(step);
+                }
+            }
+        }
+        std::mem::swap(&mut frontier, &mut next);
+    }
+    result
+}
+
+/// Since we can always step back and forth between two plots, a plot is reachable in exactly
+/// `steps` steps iff its distance is at most `steps` and has the same parity.
+fn count_reachable(distances: &[usize], steps: usize) -> usize {
+    distances
+        .iter()
+        .filter(|d| **d <= steps && **d % 2 == steps % 2)
+        .count()
+}
+
+/// Checks that the map is a square with the start in the center and with clear rows and columns
+/// through the start and around the edges. With this structure, the frontier crosses each copy of
+/// the map in the same way, so the number of reachable plots after `r + k * size` steps is a
+/// quadratic in `k`.
+fn check_structure(input: &Input) -> Result<(), String> {
+    let size = input.map.width() as i64;
+    if input.map.height() as i64 != size {
+        return Err("map is not square".into());
+    }
+    if input.start_location != (size / 2, size / 2) {
+        return Err("start is not in the center of the map".into());
+    }
+    let clear = |y: i64, x: i64| input.map[(y, x)] == Location::GardenPlot;
+    for i in [0, size / 2, size - 1] {
+        if let Some(j) = (0..size).find(|j| !clear(i, *j)) {
+            return Err(format!("row {i} is blocked at column {j}"));
+        }
+        if let Some(j) = (0..size).find(|j| !clear(*j, i)) {
+            return Err(format!("column {i} is blocked at row {j}"));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the number of garden plots reachable in exactly `steps` steps on the infinitely tiled
+/// map.
+fn reachable_tiled(input: &Input, steps: usize) -> Result<usize, String> {
+    let tiled = TiledPlane::new(&input.map);
+    let size = input.map.width();
+    let r = steps % size;
+    // We sample at 1..=4 whole map sizes past the remainder, so anything smaller than that is
+    // cheaper to count directly.
+    if steps <= r + 4 * size {
+        return Ok(count_reachable(
+            &distances(&tiled, input.start_location, steps),
+            steps,
+        ));
+    }
+    check_structure(input)?;
+
+    let distances = distances(&tiled, input.start_location, r + 4 * size);
+    let samples = (1..=4)
+        .map(|k| count_reachable(&distances, r + k * size) as i64)
+        .collect::<Vec<_>>();
+    let d1 = samples.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
+    let d2 = d1.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
+    if d2[0] != d2[1] {
+        return Err(format!("reachable plots are not quadratic: {samples:?}"));
+    }
+
+    // Newton's forward difference formula, starting from the first sample.
+    let t = (steps / size) as i64 - 1;
+    Ok((samples[0] + t * d1[0] + t * (t - 1) / 2 * d2[0]) as usize)
+}
+
+#[aoc(day21, part2)]
+fn solve_part2(input: &Input) -> Result<i64, String> {
+    Ok(reachable_tiled(input, 26501365)? as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A small map with the same structure as the real inputs: clear rows and columns through the
+    /// start and around the edges.
+    const MAP: &str = "\
+...........
+.##....##..
+..#.....#..
+.#.......#.
+....#......
+.....S.....
+.#.#...#.#.
+..#.....##.
+.##.....#..
+....#...#..
+...........";
+
+    #[test]
+    fn extrapolation_matches_brute_force() {
+        let input = generate(MAP).unwrap();
+        assert_eq!(check_structure(&input), Ok(()));
+        let tiled = TiledPlane::new(&input.map);
+        // The real inputs are 131 wide and ask about 65 + k * 131 steps.
+        for k in 5..=9 {
+            let steps = 5 + k * 11;
+            assert_eq!(
+                reachable_tiled(&input, steps),
+                Ok(reachable(&tiled, input.start_location, steps)),
+                "{steps} steps"
+            );
+        }
+    }
 }
@@ -0,0 +1,138 @@
+use crate::{lines, Grid, ParseError};
+use aoc_runner_derive::{aoc, aoc_generator};
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+struct Brick {
+    /// Corner with the smallest coordinates, as `(x, y, z)`.
+    min: (i64, i64, i64),
+    /// Corner with the largest coordinates, as `(x, y, z)`.
+    max: (i64, i64, i64),
+}
+
+impl Brick {
+    /// Returns the `(y, x)` positions this brick covers when viewed from above.
+    fn footprint(&self) -> impl Iterator<Item = (i64, i64)> {
+        let (min, max) = (self.min, self.max);
+        (min.1..=max.1).flat_map(move |y| (min.0..=max.0).map(move |x| (y, x)))
+    }
+}
+
+#[aoc_generator(day22)]
+fn generate(input: &str) -> Result<Vec<Brick>, ParseError> {
+    lines(22, input)
+        .map(|line| {
+            let (a, b) = line.split_once("~")?;
+            let corner = |s: &str| -> Result<(i64, i64, i64), ParseError> {
+                let v = s
+                    .split(',')
+                    .map(|n| line.parse(n))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match v[..] {
+                    [x, y, z] if x >= 0 && y >= 0 && z >= 1 => Ok((x, y, z)),
+                    [_, _, _] => Err(line.error(s, "coordinates out of range")),
+                    _ => Err(line.error(s, "expected \"x,y,z\"")),
+                }
+            };
+            let (a, b) = (corner(a)?, corner(b)?);
+            Ok(Brick {
+                min: (a.0.min(b.0), a.1.min(b.1), a.2.min(b.2)),
+                max: (a.0.max(b.0), a.1.max(b.1), a.2.max(b.2)),
+            })
+        })
+        .collect()
+}
+
+/// The bricks after they have all fallen as far as they can go.
+struct Settled {
+    /// For each brick, the bricks directly underneath it. Bricks are ordered from the bottom up,
+    /// so these are always smaller indices.
+    supported_by: Vec<Vec<usize>>,
+    /// For each brick, the bricks resting directly on top of it.
+    supports: Vec<Vec<usize>>,
+}
+
+fn settle(bricks: &[Brick]) -> Settled {
+    let mut bricks = bricks.to_vec();
+    bricks.sort_by_key(|b| b.min.2);
+    let width = bricks.iter().map(|b| b.max.0 + 1).max().unwrap_or(0) as usize;
+    let depth = bricks.iter().map(|b| b.max.1 + 1).max().unwrap_or(0) as usize;
+    // The height of the top of the stack at each position, along with the brick that is there.
+    let mut tops = Grid::<(i64, Option<usize>)>::filled_with((0, None), width, depth);
+    let mut supported_by = vec![vec![]; bricks.len()];
+    let mut supports = vec![vec![]; bricks.len()];
+    for (i, brick) in bricks.iter().enumerate() {
+        let rest = brick.footprint().map(|pos| tops[pos].0).max().unwrap();
+        for pos in brick.footprint() {
+            if let (top, Some(j)) = tops[pos] {
+                if top == rest && !supported_by[i].contains(&j) {
+                    supported_by[i].push(j);
+                    supports[j].push(i);
+                }
+            }
+        }
+        let height = brick.max.2 - brick.min.2 + 1;
+        for pos in brick.footprint() {
+            tops[pos] = (rest + height, Some(i));
+        }
+    }
+    Settled {
+        supported_by,
+        supports,
+    }
+}
+
+#[aoc(day22, part1)]
+fn solve_part1(input: &[Brick]) -> usize {
+    let settled = settle(input);
+    // A brick can be removed if everything it supports has some other support.
+    settled
+        .supports
+        .iter()
+        .filter(|above| above.iter().all(|j| settled.supported_by[*j].len() > 1))
+        .count()
+}
+
+#[aoc(day22, part2)]
+fn solve_part2(input: &[Brick]) -> usize {
+    let settled = settle(input);
+    let n = settled.supports.len();
+    let mut falling = vec![false; n];
+    (0..n)
+        .map(|i| {
+            falling.fill(false);
+            falling[i] = true;
+            let mut count = 0;
+            // Since bricks are ordered from the bottom up, everything a brick rests on has been
+            // decided by the time we get to it.
+            for j in i + 1..n {
+                let below = &settled.supported_by[j];
+                if !below.is_empty() && below.iter().all(|k| falling[*k]) {
+                    falling[j] = true;
+                    count += 1;
+                }
+            }
+            count
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+1,0,1~1,2,1
+0,0,2~2,0,2
+0,2,3~2,2,3
+0,0,4~0,2,4
+2,0,5~2,2,5
+0,1,6~2,1,6
+1,1,8~1,1,9";
+
+    #[test]
+    fn example() {
+        let bricks = generate(EXAMPLE).unwrap();
+        assert_eq!(solve_part1(&bricks), 5);
+        assert_eq!(solve_part2(&bricks), 7);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::{Dir, Grid, ParseError, Plane};
+use aoc_runner_derive::{aoc, aoc_generator};
+use std::collections::HashMap;
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+enum Tile {
+    Path,
+    Forest,
+    /// A steep slope, which can only be left in the given direction.
+    Slope(Dir),
+}
+
+impl Tile {
+    /// Returns true iff we are allowed to step off this tile in direction `dir`.
+    fn exits_toward(self, dir: Dir, slippery: bool) -> bool {
+        match self {
+            Tile::Path => true,
+            Tile::Forest => false,
+            Tile::Slope(d) => !slippery || d == dir,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Input {
+    map: Grid<Tile>,
+    start: (i64, i64),
+    end: (i64, i64),
+}
+
+#[aoc_generator(day23)]
+fn generate(input: &str) -> Result<Input, ParseError> {
+    let map = Grid::parse(23, input, |c| match c {
+        '.' => Some(Tile::Path),
+        '#' => Some(Tile::Forest),
+        '^' => Some(Tile::Slope(Dir::N)),
+        'v' => Some(Tile::Slope(Dir::S)),
+        '>' => Some(Tile::Slope(Dir::E)),
+        '<' => Some(Tile::Slope(Dir::W)),
+        _ => None,
+    })?;
+    let row_entrance = |y: i64, what: &str| -> Result<(i64, i64), ParseError> {
+        let x = map
+            .row(y)
+            .iter()
+            .position(|t| *t == Tile::Path)
+            .ok_or_else(|| ParseError::input(23, format!("no {what} in row {}", y + 1)))?;
+        Ok((y, x as i64))
+    };
+    let start = row_entrance(0, "start")?;
+    let end = row_entrance(map.height() as i64 - 1, "end")?;
+    Ok(Input { map, start, end })
+}
+
+/// The trail map reduced to the places where a choice has to be made, and the lengths of the
+/// corridors between them.
+struct Graph {
+    /// For each junction, the junctions reachable from it along with the length of the way there.
+    edges: Vec<Vec<(usize, usize)>>,
+    start: usize,
+    end: usize,
+    /// Whether only one junction leads to the end.
+    single_exit: bool,
+}
+
+impl Graph {
+    fn new(input: &Input, slippery: bool) -> Self {
+        let map = &input.map;
+        let open = |pos: &(i64, i64)| map[*pos] != Tile::Forest;
+        let junctions = map
+            .positions()
+            .filter(|pos| {
+                *pos == input.start
+                    || *pos == input.end
+                    || (open(pos) && Dir::neighbors(*pos, map).filter(open).count() > 2)
+            })
+            .enumerate()
+            .map(|(i, pos)| (pos, i))
+            .collect::<HashMap<_, _>>();
+        let mut edges = vec![vec![]; junctions.len()];
+        for (&from, &i) in &junctions {
+            for dir in [Dir::N, Dir::S, Dir::E, Dir::W] {
+                if !map[from].exits_toward(dir, slippery) {
+                    continue;
+                }
+                let Some(mut pos) = dir.neighbor(from, map).filter(open) else {
+                    continue;
+                };
+                let mut prev = from;
+                let mut len = 1;
+                // Follow the corridor until it reaches another junction or becomes impassable.
+                while !junctions.contains_key(&pos) {
+                    let next = [Dir::N, Dir::S, Dir::E, Dir::W].into_iter().find_map(|d| {
+                        d.neighbor(pos, map)
+                            .filter(|n| *n != prev && open(n))
+                            .map(|n| (d, n))
+                    });
+                    match next {
+                        Some((d, n)) if map[pos].exits_toward(d, slippery) => {
+                            prev = pos;
+                            pos = n;
+                            len += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                if let Some(&j) = junctions.get(&pos) {
+                    edges[i].push((j, len));
+                }
+            }
+        }
+        let end = junctions[&input.end];
+        let single_exit = edges
+            .iter()
+            .filter(|e| e.iter().any(|(j, _)| *j == end))
+            .count()
+            == 1;
+        Graph {
+            start: junctions[&input.start],
+            end,
+            edges,
+            single_exit,
+        }
+    }
+
+    /// Returns the length of the longest path from `node` to the end that avoids everything in
+    /// `visited`, if there is one.
+    fn longest(&self, node: usize, visited: &mut [bool]) -> Option<usize> {
+        if node == self.end {
+            return Some(0);
+        }
+        // If this is the only way to the end, we have to go there now -- otherwise we would cut
+        // ourselves off from it.
+        if self.single_exit {
+            if let Some((_, len)) = self.edges[node].iter().find(|(j, _)| *j == self.end) {
+                return Some(*len);
+            }
+        }
+        visited[node] = true;
+        let best = self.edges[node]
+            .iter()
+            .filter_map(|&(next, len)| {
+                if visited[next] {
+                    None
+                } else {
+                    self.longest(next, visited).map(|rest| rest + len)
+                }
+            })
+            .max();
+        visited[node] = false;
+        best
+    }
+
+    fn longest_hike(&self) -> Option<usize> {
+        self.longest(self.start, &mut vec![false; self.edges.len()])
+    }
+}
+
+#[aoc(day23, part1)]
+fn solve_part1(input: &Input) -> Option<usize> {
+    Graph::new(input, true).longest_hike()
+}
+
+#[aoc(day23, part2)]
+fn solve_part2(input: &Input) -> Option<usize> {
+    Graph::new(input, false).longest_hike()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+#.#####################
+#.......#########...###
+#######.#########.#.###
+###.....#.>.>.###.#.###
+###v#####.#v#.###.#.###
+###.>...#.#.#.....#...#
+###v###.#.#.#########.#
+###...#.#.#.......#...#
+#####.#.#.#######.#.###
+#.....#.#.#.......#...#
+#.#####.#.#.#########v#
+#.#...#...#...###...>.#
+#.#.#v#######v###.###v#
+#...#.>.#...>.>.#.###.#
+#####v#.#.###v#.#.###.#
+#.....#...#...#.#.#...#
+#.#########.###.#.#.###
+#...###...#...#...#.###
+###.###.#.###v#####v###
+#...#...#.#.>.>.#.>.###
+#.###.###.#.###.#.#v###
+#.....###...###...#...#
+#####################.#";
+
+    #[test]
+    fn example() {
+        let input = generate(EXAMPLE).unwrap();
+        assert_eq!(solve_part1(&input), Some(94));
+        assert_eq!(solve_part2(&input), Some(154));
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{lines, ParseError};
+use aoc_runner_derive::{aoc, aoc_generator};
+
+type Vec3 = [i64; 3];
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+struct Hailstone {
+    pos: Vec3,
+    vel: Vec3,
+}
+
+#[aoc_generator(day24)]
+fn generate(input: &str) -> Result<Vec<Hailstone>, ParseError> {
+    lines(24, input)
+        .map(|line| {
+            let (pos, vel) = line.split_once(" @ ")?;
+            let vec3 = |s: &str| -> Result<Vec3, ParseError> {
+                let v = s
+                    .split(',')
+                    .map(|n| line.parse(n.trim()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                v.try_into()
+                    .map_err(|_| line.error(s, "expected \"x, y, z\""))
+            };
+            Ok(Hailstone {
+                pos: vec3(pos)?,
+                vel: vec3(vel)?,
+            })
+        })
+        .collect()
+}
+
+/// Counts the pairs of hailstones whose future paths cross inside the square `min..=max`,
+/// ignoring the z axis.
+fn count_crossings(hail: &[Hailstone], min: i64, max: i64) -> usize {
+    let (min, max) = (min as i128, max as i128);
+    let mut count = 0;
+    for (i, a) in hail.iter().enumerate() {
+        for b in &hail[i + 1..] {
+            let [ax, ay, _] = a.pos.map(|n| n as i128);
+            let [avx, avy, _] = a.vel.map(|n| n as i128);
+            let [bx, by, _] = b.pos.map(|n| n as i128);
+            let [bvx, bvy, _] = b.vel.map(|n| n as i128);
+            // Solve a.pos + ta * a.vel = b.pos + tb * b.vel with Cramer's rule. Everything is
+            // kept as a fraction over `det` so that the comparisons are exact.
+            let mut det = bvx * avy - avx * bvy;
+            if det == 0 {
+                // Parallel paths never cross.
+                continue;
+            }
+            let (dx, dy) = (bx - ax, by - ay);
+            let mut ta = bvx * dy - bvy * dx;
+            let mut tb = avx * dy - avy * dx;
+            if det < 0 {
+                (det, ta, tb) = (-det, -ta, -tb);
+            }
+            if ta < 0 || tb < 0 {
+                // The crossing is in the past for at least one of them.
+                continue;
+            }
+            let x = ax * det + avx * ta;
+            let y = ay * det + avy * ta;
+            if (min * det..=max * det).contains(&x) && (min * det..=max * det).contains(&y) {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+#[aoc(day24, part1)]
+fn solve_part1(input: &[Hailstone]) -> usize {
+    count_crossings(input, 200000000000000, 400000000000000)
+}
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+/// Solves the linear system `m * x = rhs` with Gaussian elimination.
+fn solve_linear<const N: usize>(mut m: [[f64; N]; N], mut rhs: [f64; N]) -> Option<[f64; N]> {
+    for col in 0..N {
+        let pivot = (col..N).max_by(|a, b| m[*a][col].abs().total_cmp(&m[*b][col].abs()))?;
+        if m[pivot][col] == 0.0 {
+            return None;
+        }
+        m.swap(col, pivot);
+        rhs.swap(col, pivot);
+        for row in col + 1..N {
+            let f = m[row][col] / m[col][col];
+            let (above, below) = m.split_at_mut(row);
+            for (a, b) in below[0][col..].iter_mut().zip(&above[col][col..]) {
+                *a -= f * b;
+            }
+            rhs[row] -= f * rhs[col];
+        }
+    }
+    let mut x = [0.0; N];
+    for row in (0..N).rev() {
+        let s = (row + 1..N).map(|k| m[row][k] * x[k]).sum::<f64>();
+        x[row] = (rhs[row] - s) / m[row][row];
+    }
+    Some(x)
+}
+
+/// Finds the rock velocity by solving a linear system built from three hailstones.
+///
+/// If the rock starts at `p` with velocity `v`, it hits hailstone `i` iff `(p - p_i)` is parallel
+/// to `(v - v_i)`, i.e. `(p - p_i) x (v - v_i) = 0`. The `p x v` term is the same for every
+/// hailstone, so subtracting the equations for two hailstones `i` and `j` leaves
+///
+/// p x (v_i - v_j) + (p_i - p_j) x v = p_i x v_i - p_j x v_j
+///
+/// which is linear in `p` and `v`. Two pairs give us six equations in six unknowns.
+fn rock_velocity(hail: &[Hailstone]) -> Option<Vec3> {
+    let [h0, h1, h2] = [hail.first()?, hail.get(1)?, hail.get(2)?];
+    let mut m = [[0.0; 6]; 6];
+    let mut rhs = [0.0; 6];
+    for (k, h) in [h1, h2].into_iter().enumerate() {
+        let dv = sub(h0.vel, h.vel).map(|n| n as f64);
+        let dp = sub(h0.pos, h.pos).map(|n| n as f64);
+        // `p x dv` and `dp x v`, written out as rows of a matrix acting on `(p, v)`.
+        let rows = [
+            [0.0, dv[2], -dv[1], 0.0, -dp[2], dp[1]],
+            [-dv[2], 0.0, dv[0], dp[2], 0.0, -dp[0]],
+            [dv[1], -dv[0], 0.0, -dp[1], dp[0], 0.0],
+        ];
+        let c0 = cross(h0.pos, h0.vel).map(|n| n as i128);
+        let c = cross(h.pos, h.vel).map(|n| n as i128);
+        for i in 0..3 {
+            m[k * 3 + i] = rows[i];
+            rhs[k * 3 + i] = (c0[i] - c[i]) as f64;
+        }
+    }
+    let x = solve_linear(m, rhs)?;
+    Some([x[3], x[4], x[5]].map(|n| n.round() as i64))
+}
+
+/// Given the rock's velocity, finds where it has to start to hit every hailstone, using exact
+/// arithmetic.
+fn rock_position(hail: &[Hailstone], vel: Vec3) -> Option<Vec3> {
+    // In the rock's frame of reference, every hailstone passes through the rock's starting
+    // position, so that's where the paths of any two of them cross.
+    let a = hail.first()?;
+    let av = sub(a.vel, vel).map(|n| n as i128);
+    let ap = a.pos.map(|n| n as i128);
+    for b in &hail[1..] {
+        let bv = sub(b.vel, vel).map(|n| n as i128);
+        let d = sub(b.pos, a.pos).map(|n| n as i128);
+        for (i, j) in [(0, 1), (0, 2), (1, 2)] {
+            let det = bv[i] * av[j] - av[i] * bv[j];
+            if det == 0 {
+                continue;
+            }
+            let t = bv[i] * d[j] - bv[j] * d[i];
+            if t % det != 0 {
+                return None;
+            }
+            let t = t / det;
+            return Some([0, 1, 2].map(|k| (ap[k] + t * av[k]) as i64));
+        }
+    }
+    None
+}
+
+#[aoc(day24, part2)]
+fn solve_part2(input: &[Hailstone]) -> Result<i64, String> {
+    let vel = rock_velocity(input).ok_or("couldn't solve for the rock's velocity")?;
+    let pos = rock_position(input, vel).ok_or("couldn't solve for the rock's position")?;
+    // Check that the rock really does hit everything at some point in the future.
+    for h in input {
+        let dp = sub(h.pos, pos);
+        let dv = sub(vel, h.vel);
+        let hits = (0..3)
+            .map(|k| match (dp[k], dv[k]) {
+                (0, 0) => Some(None),
+                (_, 0) => None,
+                (p, v) if p % v == 0 && p / v >= 0 => Some(Some(p / v)),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| format!("rock at {pos:?} moving {vel:?} misses {h:?}"))?;
+        let mut times = hits.into_iter().flatten();
+        if let Some(t) = times.next() {
+            if times.any(|u| u != t) {
+                return Err(format!("rock at {pos:?} moving {vel:?} misses {h:?}"));
+            }
+        }
+    }
+    Ok(pos.iter().sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+19, 13, 30 @ -2,  1, -2
+18, 19, 22 @ -1, -1, -2
+20, 25, 34 @ -2, -2, -4
+12, 31, 28 @ -1, -2, -1
+20, 19, 15 @  1, -5, -3";
+
+    #[test]
+    fn example() {
+        let hail = generate(EXAMPLE).unwrap();
+        assert_eq!(count_crossings(&hail, 7, 27), 2);
+        assert_eq!(solve_part2(&hail), Ok(47));
+    }
+}
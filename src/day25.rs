@@ -0,0 +1,136 @@
+use crate::{lines, ParseError};
+use aoc_runner_derive::{aoc, aoc_generator};
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Clone, Debug)]
+struct Input {
+    /// For each component, the components it is wired to. Wires go both ways.
+    wires: Vec<Vec<usize>>,
+}
+
+#[aoc_generator(day25)]
+fn generate(input: &str) -> Result<Input, ParseError> {
+    let mut ids = HashMap::new();
+    let mut wires: Vec<Vec<usize>> = vec![];
+    let mut id = |name: &str, wires: &mut Vec<Vec<usize>>| {
+        *ids.entry(name.to_string()).or_insert_with(|| {
+            wires.push(vec![]);
+            wires.len() - 1
+        })
+    };
+    for line in lines(25, input) {
+        let (from, to) = line.split_once(": ")?;
+        let from = id(from, &mut wires);
+        for name in to.split_whitespace() {
+            let to = id(name, &mut wires);
+            wires[from].push(to);
+            wires[to].push(from);
+        }
+    }
+    Ok(Input { wires })
+}
+
+/// Pushes flow along the shortest augmenting path from `source` to `sink`, returning false if
+/// there isn't one. Every wire has a capacity of one in each direction.
+fn augment(
+    wires: &[Vec<usize>],
+    flow: &mut HashMap<(usize, usize), i32>,
+    source: usize,
+    sink: usize,
+) -> bool {
+    let mut prev = vec![None; wires.len()];
+    let mut queue = VecDeque::from([source]);
+    prev[source] = Some(source);
+    while let Some(node) = queue.pop_front() {
+        if node == sink {
+            break;
+        }
+        for &next in &wires[node] {
+            if prev[next].is_none() && flow.get(&(node, next)).copied().unwrap_or(0) < 1 {
+                prev[next] = Some(node);
+                queue.push_back(next);
+            }
+        }
+    }
+    if prev[sink].is_none() {
+        return false;
+    }
+    let mut node = sink;
+    while node != source {
+        let p = prev[node].unwrap();
+        *flow.entry((p, node)).or_default() += 1;
+        *flow.entry((node, p)).or_default() -= 1;
+        node = p;
+    }
+    true
+}
+
+/// Returns the components that can still be reached from `source` without saturated wires.
+fn residual_reachable(
+    wires: &[Vec<usize>],
+    flow: &HashMap<(usize, usize), i32>,
+    source: usize,
+) -> usize {
+    let mut seen = vec![false; wires.len()];
+    let mut stack = vec![source];
+    seen[source] = true;
+    let mut count = 1;
+    while let Some(node) = stack.pop() {
+        for &next in &wires[node] {
+            if !seen[next] && flow.get(&(node, next)).copied().unwrap_or(0) < 1 {
+                seen[next] = true;
+                count += 1;
+                stack.push(next);
+            }
+        }
+    }
+    count
+}
+
+#[aoc(day25, part1)]
+fn solve_part1(input: &Input) -> Result<usize, String> {
+    const CUT: usize = 3;
+    let wires = &input.wires;
+    let n = wires.len();
+    // Some component must be on the other side of the cut from component 0. By max-flow/min-cut,
+    // that's one which we can only push `CUT` units of flow to.
+    for sink in 1..n {
+        let mut flow = HashMap::new();
+        let mut total = 0;
+        while total <= CUT && augment(wires, &mut flow, 0, sink) {
+            total += 1;
+        }
+        if total == CUT {
+            let size = residual_reachable(wires, &flow, 0);
+            return Ok(size * (n - size));
+        }
+    }
+    Err(format!(
+        "no cut of {CUT} wires splits the components in two"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+jqt: rhn xhk nvd
+rsh: frs pzl lsr
+xhk: hfx
+cmg: qnr nvd lhk bvb
+rhn: xhk bvb hfx
+bvb: xhk hfx
+pzl: lsr hfx nvd
+qnr: nvd
+ntq: jqt hfx bvb xhk
+nvd: lhk
+lsr: lhk
+rzs: qnr cmg lsr rsh
+frs: qnr lhk lsr";
+
+    #[test]
+    fn example() {
+        assert_eq!(solve_part1(&generate(EXAMPLE).unwrap()), Ok(54));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::{surrounding, Grid, ParseError, Plane};
 use aoc_runner_derive::{aoc, aoc_generator};
 
 #[derive(Debug)]
@@ -8,45 +9,16 @@ struct NumberSpan {
 }
 
 impl NumberSpan {
-    fn parse(&self, grid: &Box<[Box<[char]>]>) -> Option<u32> {
-        grid[self.y][self.x..(self.x + self.len)]
+    fn parse(&self, grid: &Grid<char>) -> Option<u32> {
+        grid.row(self.y as i64)[self.x..(self.x + self.len)]
             .iter()
             .collect::<String>()
             .parse()
             .ok()
     }
 
-    fn neighbors(&self, (width, height): (usize, usize)) -> impl Iterator<Item = (usize, usize)> {
-        let mut v = vec![];
-        if self.y < height - 1 {
-            for i in 0..self.len {
-                v.push((self.x + i, self.y + 1))
-            }
-        }
-        if self.y > 0 {
-            for i in 0..self.len {
-                v.push((self.x + i, self.y - 1))
-            }
-        }
-        if self.x > 0 {
-            v.push((self.x - 1, self.y));
-            if self.y > 0 {
-                v.push((self.x - 1, self.y - 1))
-            }
-            if self.y < height - 1 {
-                v.push((self.x - 1, self.y + 1))
-            }
-        }
-        if self.x + self.len < width {
-            v.push((self.x + self.len, self.y));
-            if self.y > 0 {
-                v.push((self.x + self.len, self.y - 1))
-            }
-            if self.y < height - 1 {
-                v.push((self.x + self.len, self.y + 1))
-            }
-        }
-        v.into_iter()
+    fn neighbors<'a, P: Plane>(&self, p: &'a P) -> impl Iterator<Item = (i64, i64)> + 'a {
+        surrounding((self.y as i64, self.x as i64), self.len, p)
     }
 }
 
@@ -68,34 +40,8 @@ struct SymbolLocation {
 }
 
 impl SymbolLocation {
-    fn neighbors(&self, (width, height): (usize, usize)) -> impl Iterator<Item = (usize, usize)> {
-        let mut v = vec![];
-        if self.x > 0 {
-            v.push((self.x - 1, self.y));
-            if self.y > 0 {
-                v.push((self.x - 1, self.y - 1));
-            }
-            if self.y + 1 < height {
-                v.push((self.x - 1, self.y + 1));
-            }
-        }
-        if self.x + 1 < width {
-            v.push((self.x + 1, self.y));
-            if self.y > 0 {
-                v.push((self.x + 1, self.y - 1));
-            }
-            if self.y + 1 < height {
-                v.push((self.x + 1, self.y + 1));
-            }
-        }
-        if self.y > 0 {
-            v.push((self.x, self.y - 1));
-        }
-        if self.y + 1 < height {
-            v.push((self.x, self.y + 1));
-        }
-
-        v.into_iter()
+    fn neighbors<'a, P: Plane>(&self, p: &'a P) -> impl Iterator<Item = (i64, i64)> + 'a {
+        surrounding((self.y as i64, self.x as i64), 1, p)
     }
 }
 
@@ -164,43 +110,36 @@ impl StateMachine {
 struct PuzzleInput {
     numbers: Vec<NumberSpan>,
     symbols: Vec<SymbolLocation>,
-    grid: Box<[Box<[char]>]>,
-    width: usize,
-    height: usize,
+    grid: Grid<char>,
 }
 
 #[aoc_generator(day3)]
-fn generate<'a>(input: &str) -> PuzzleInput {
+fn generate(input: &str) -> Result<PuzzleInput, ParseError> {
+    let grid = Grid::parse(3, input, |c| match c {
+        '\0' => None,
+        _ => Some(c),
+    })?;
+
     let mut machine = StateMachine::new();
-    for line in input.lines() {
-        for c in line.chars().chain(['\0'].into_iter()) {
+    for row in grid.rows() {
+        for c in row.iter().copied().chain(['\0']) {
             machine.process(c);
         }
     }
 
-    let grid = input
-        .lines()
-        .map(|line| line.chars().collect::<Box<[_]>>())
-        .collect::<Box<[_]>>();
-
-    PuzzleInput {
+    Ok(PuzzleInput {
         numbers: machine.numbers,
         symbols: machine.symbols,
-        width: grid[0].len(),
-        height: grid.len(),
         grid,
-    }
+    })
 }
 
 #[aoc(day3, part1)]
 fn solve_part1(input: &PuzzleInput) -> u32 {
     let mut sum = 0;
     for span in &input.numbers {
-        for (x, y) in span.neighbors((input.width, input.height)) {
-            if match input.grid[y][x] {
-                '.' | '0'..='9' => false,
-                _ => true,
-            } {
+        for pos in span.neighbors(&input.grid) {
+            if !matches!(input.grid[pos], '.' | '0'..='9') {
                 sum += span.parse(&input.grid).unwrap();
                 break;
             }
@@ -214,21 +153,22 @@ use std::collections::BTreeSet;
 #[aoc(day3, part2)]
 fn solve_part2(input: &PuzzleInput) -> u32 {
     let mut sum = 0;
-    let mut num_grid: Vec<Vec<Option<usize>>> = vec![vec![None; input.width]; input.height];
+    let mut num_grid =
+        Grid::<Option<usize>>::filled_with(None, input.grid.width(), input.grid.height());
     for (span_id, span) in input.numbers.iter().enumerate() {
         for i in 0..span.len {
-            num_grid[span.y][span.x + i] = Some(span_id);
+            num_grid[(span.y as i64, (span.x + i) as i64)] = Some(span_id);
         }
     }
 
     for sym in input
         .symbols
         .iter()
-        .filter(|sym| input.grid[sym.y][sym.x] == '*')
+        .filter(|sym| input.grid[(sym.y as i64, sym.x as i64)] == '*')
     {
         let neighboring_numbers = sym
-            .neighbors((input.width, input.height))
-            .filter_map(|(x, y)| num_grid[y][x])
+            .neighbors(&input.grid)
+            .filter_map(|pos| num_grid[pos])
             .collect::<BTreeSet<usize>>();
         if neighboring_numbers.len() == 2 {
             sum += neighboring_numbers
@@ -1,3 +1,4 @@
+use crate::{lines, ParseError};
 use aoc_runner_derive::{aoc, aoc_generator};
 
 #[derive(Debug)]
@@ -7,30 +8,23 @@ struct Card {
 }
 
 #[aoc_generator(day4)]
-fn generate(input: &str) -> Vec<Card> {
-    input
-        .lines()
+fn generate(input: &str) -> Result<Vec<Card>, ParseError> {
+    lines(4, input)
         .map(|line| {
-            let line = line
-                .split(": ")
-                .last()
-                .unwrap()
-                .split("|")
-                .collect::<Vec<_>>();
-            Card {
-                winning_numbers: line[0]
-                    .split(" ")
-                    .filter(|s| s.len() > 0)
-                    .map(|s| s.parse())
-                    .collect::<Result<_, _>>()
-                    .unwrap(),
-                numbers: line[1]
-                    .split(" ")
-                    .filter(|s| s.len() > 0)
-                    .map(|s| s.parse())
-                    .collect::<Result<_, _>>()
-                    .unwrap(),
-            }
+            let (_, numbers) = line.split_once(": ")?;
+            let (winning_numbers, numbers) = numbers
+                .split_once('|')
+                .ok_or_else(|| line.error_at_end("expected '|'"))?;
+            Ok(Card {
+                winning_numbers: winning_numbers
+                    .split_whitespace()
+                    .map(|s| line.parse(s))
+                    .collect::<Result<_, _>>()?,
+                numbers: numbers
+                    .split_whitespace()
+                    .map(|s| line.parse(s))
+                    .collect::<Result<_, _>>()?,
+            })
         })
         .collect()
 }
@@ -38,7 +32,7 @@ fn generate(input: &str) -> Vec<Card> {
 use std::collections::BTreeSet;
 
 #[aoc(day4, part1)]
-fn solve_part1(input: &Vec<Card>) -> u32 {
+fn solve_part1(input: &[Card]) -> u32 {
     input
         .iter()
         .map(|card| {
@@ -55,14 +49,13 @@ fn solve_part1(input: &Vec<Card>) -> u32 {
 }
 
 #[aoc(day4, part2)]
-fn solve_part2(input: &Vec<Card>) -> u32 {
+fn solve_part2(input: &[Card]) -> u32 {
     let hits = input
         .iter()
         .map(|card| {
             let numbers: BTreeSet<u32> = card.numbers.iter().cloned().collect();
             let winning_numbers: BTreeSet<u32> = card.winning_numbers.iter().cloned().collect();
-            let hits = numbers.intersection(&winning_numbers).count();
-            hits
+            numbers.intersection(&winning_numbers).count()
         })
         .collect::<Vec<_>>();
     let mut instances = vec![1; hits.len()];
@@ -1,174 +1,170 @@
+use crate::ranges::{Offset, PiecewiseOffsetMap, Range, RangeSet};
+use crate::{lines, ParseError};
 use aoc_runner_derive::{aoc, aoc_generator};
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd)]
-struct MapRange {
-    source: i64,
-    dest: i64,
-    len: i64,
-}
-
 #[derive(Clone, Debug)]
 struct Almanac {
     seeds: Vec<i64>,
-    maps: Vec<Vec<MapRange>>,
+    /// The maps from seed to soil, soil to fertilizer, and so on, in order.
+    maps: Vec<PiecewiseOffsetMap>,
+}
+
+impl Almanac {
+    /// All of the maps composed into one, from seed to location.
+    fn seed_to_location(&self) -> PiecewiseOffsetMap {
+        self.maps
+            .iter()
+            .fold(PiecewiseOffsetMap::identity(), |chain, map| chain.then(map))
+    }
+
+    /// Every seed that ends up at one of `locations`, found by pulling the locations back
+    /// through each map in turn.
+    fn seeds_for(&self, locations: &RangeSet) -> RangeSet {
+        self.maps
+            .iter()
+            .rev()
+            .fold(locations.clone(), |set, map| map.preimage(&set))
+    }
 }
 
 #[aoc_generator(day5)]
-fn generate(input: &str) -> Almanac {
-    let lines = input.lines().filter(|s| s.len() > 0).collect::<Vec<_>>();
-    let seeds = lines[0]["seeds: ".len()..]
-        .split(" ")
-        .map(|s| s.parse())
-        .collect::<Result<Vec<_>, _>>()
-        .unwrap();
+fn generate(input: &str) -> Result<Almanac, ParseError> {
+    let lines = lines(5, input)
+        .filter(|line| !line.text.is_empty())
+        .collect::<Vec<_>>();
+    let header = lines
+        .first()
+        .ok_or_else(|| ParseError::input(5, "empty input"))?;
+    let seeds = header
+        .text
+        .strip_prefix("seeds: ")
+        .ok_or_else(|| header.error(header.text, "expected \"seeds: \""))?
+        .split_whitespace()
+        .map(|s| header.parse(s))
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(line) = lines.get(1) {
+        if !line.text.contains("map:") {
+            return Err(line.error(line.text, "expected a map header"));
+        }
+    }
     let maps = lines[1..]
-        .split(|line| line.contains("map:"))
+        .split(|line| line.text.contains("map:"))
         .skip(1)
         .map(|chunk| {
             chunk
                 .iter()
                 .map(|line| {
-                    let mut it = line.split(" ").map(|s| s.parse().unwrap());
-                    MapRange {
-                        dest: it.next().unwrap(),
-                        source: it.next().unwrap(),
-                        len: it.next().unwrap(),
+                    let fields = line
+                        .text
+                        .split_whitespace()
+                        .map(|s| line.parse(s))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    match fields[..] {
+                        [dest, source, len] => Ok(Offset {
+                            start: source,
+                            end: source + len,
+                            diff: dest - source,
+                        }),
+                        _ => Err(line.error(line.text, "expected \"<dest> <source> <len>\"")),
                     }
                 })
-                .collect::<Vec<_>>()
+                .collect::<Result<Vec<_>, _>>()
+                .map(PiecewiseOffsetMap::from_offsets)
         })
-        .collect::<Vec<_>>();
-    Almanac { seeds, maps }
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Almanac { seeds, maps })
 }
 
 #[aoc(day5, part1)]
 fn solve_part1(input: &Almanac) -> i64 {
-    let Almanac { seeds, maps } = input;
-    seeds
+    let chain = input.seed_to_location();
+    input
+        .seeds
         .iter()
-        .map(|seed| {
-            let mut cur = *seed;
-            for map in maps {
-                for range in map {
-                    if cur >= range.source && cur < range.source + range.len {
-                        cur = range.dest + (cur - range.source);
-                        break;
-                    }
-                }
-            }
-            cur
-        })
+        .map(|seed| chain.apply(*seed))
         .min()
         .unwrap()
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd)]
-struct Offset {
-    start: i64,
-    end: i64,
-    diff: i64,
+#[aoc(day5, part2)]
+fn solve_part2(input: &Almanac) -> i64 {
+    let seeds = RangeSet::from_ranges(input.seeds.chunks(2).map(|v| Range::new(v[0], v[0] + v[1])));
+    input.seed_to_location().image(&seeds).min().unwrap()
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd)]
-struct Range {
-    start: i64,
-    end: i64,
+/// Parses an almanac and returns the ranges of seed numbers that end up at one of `locations`.
+/// This includes seeds that aren't listed at the top of the almanac, so intersect the result with
+/// those if that's what you're after.
+pub fn seeds_for_locations(input: &str, locations: &RangeSet) -> Result<RangeSet, ParseError> {
+    Ok(generate(input)?.seeds_for(locations))
 }
 
-#[aoc(day5, part2)]
-fn solve_part2(input: &Almanac) -> i64 {
-    let Almanac { seeds, maps } = input;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut seeds = seeds
-        .chunks(2)
-        .map(|v| Range {
-            start: v[0],
-            end: v[0] + v[1],
-        })
-        .collect::<Vec<_>>();
+    const EXAMPLE: &str = "\
+seeds: 79 14 55 13
 
-    let mut next = vec![];
+seed-to-soil map:
+50 98 2
+52 50 48
 
-    let maps = maps
-        .into_iter()
-        .map(|map| {
-            map.into_iter()
-                .map(|m| Offset {
-                    start: m.source,
-                    end: m.source + m.len,
-                    diff: m.dest - m.source,
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
 
-    for map in maps {
-        let mut map = map.clone();
-        seeds.sort_by_key(|s| s.start);
-        map.sort_by_key(|m| m.start);
-        let mut map_it = map.into_iter().peekable();
-        for seed in &seeds {
-            let mut seed = seed.clone();
-            loop {
-                match map_it.peek_mut() {
-                    Some(m) => {
-                        if seed.start < m.start && seed.end < m.start {
-                            // No map for this range -- pass through unchanged.
-                            next.push(seed);
-                            break;
-                        } else if seed.start < m.start {
-                            // We need to split this into an unmapped range and a mapped range.
-                            next.push(Range {
-                                start: seed.start,
-                                end: m.start,
-                            });
-                            // Fast forward so we match the start of the map range -- the second
-                            // part of the split will be handled by the branch immediately follwing
-                            // this one (on the next iteration).
-                            seed.start = m.start;
-                        } else if seed.start == m.start {
-                            if seed.end <= m.end {
-                                // The full input range is mapped through this map, so we're done.
-                                next.push(Range {
-                                    start: seed.start + m.diff,
-                                    end: seed.end + m.diff,
-                                });
-                                break;
-                            } else {
-                                // Otherwise, we need to split the range.
-                                next.push(Range {
-                                    start: seed.start + m.diff,
-                                    end: m.end + m.diff,
-                                });
-                                // Again, the second part of the split will be handled by the next
-                                // iteration of the loop.
-                                seed.start = m.end;
-                            }
-                        } else if seed.start < m.end {
-                            // We have an unused chunk of the map, so we can drop the unused
-                            // portion and hit the `seed.start == m.start` case on the next
-                            // iteration of the loop.
-                            *m = Offset {
-                                start: seed.start,
-                                end: m.end,
-                                diff: m.diff,
-                            };
-                        } else {
-                            // Nothing maps through this one -- skip.
-                            map_it.next();
-                        }
-                    }
-                    None => {
-                        // If we're out of maps to look at, pass the input through unchanged.
-                        next.push(seed);
-                        break;
-                    }
-                }
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4
+";
+
+    #[test]
+    fn seeds_for_locations_agrees_with_apply() {
+        let almanac = generate(EXAMPLE).unwrap();
+        let map = almanac.seed_to_location();
+        let listed = RangeSet::from_ranges(almanac.seeds.iter().map(|&s| Range::new(s, s + 1)));
+        for locations in [Range::new(0, 1000), Range::new(40, 85)] {
+            let seeds = seeds_for_locations(EXAMPLE, &locations.into()).unwrap();
+            let found = seeds.intersection(&listed);
+            for &seed in &almanac.seeds {
+                assert_eq!(
+                    found.contains(seed),
+                    locations.contains(map.apply(seed)),
+                    "seed {seed} in {locations:?}"
+                );
             }
         }
-        std::mem::swap(&mut seeds, &mut next);
-        next.clear();
     }
 
-    seeds.iter().map(|s| s.start).min().unwrap()
+    #[test]
+    fn seeds_for_exact_locations() {
+        // The locations of the listed seeds, from the puzzle description.
+        for (seed, location) in [(79, 82), (14, 43), (55, 86), (13, 35)] {
+            assert_eq!(
+                seeds_for_locations(EXAMPLE, &Range::new(location, location + 1).into()),
+                Ok(Range::new(seed, seed + 1).into())
+            );
+        }
+    }
 }
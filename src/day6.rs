@@ -1,3 +1,4 @@
+use crate::{lines, Line, ParseError};
 use aoc_runner_derive::{aoc, aoc_generator};
 
 #[derive(Clone, Debug)]
@@ -6,50 +7,49 @@ struct Race {
     distance: i64,
 }
 
+/// Parses a `Time:` or `Distance:` line, returning both the individual values and the value
+/// obtained by ignoring the spaces between them.
+fn parse_line(line: Option<Line>, label: &str) -> Result<(Vec<i64>, i64), ParseError> {
+    let line = line.ok_or_else(|| ParseError::input(6, format!("missing {label:?} line")))?;
+    let values = line
+        .text
+        .strip_prefix(label)
+        .ok_or_else(|| line.error(line.text, format!("expected {label:?}")))?;
+    let combined = values
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>();
+    Ok((
+        values
+            .split_whitespace()
+            .map(|s| line.parse(s))
+            .collect::<Result<_, _>>()?,
+        combined
+            .parse()
+            .map_err(|e| line.error(values, format!("{e}")))?,
+    ))
+}
+
 #[aoc_generator(day6)]
-fn generate(input: &str) -> (Vec<Race>, Race) {
-    let lines = input.lines().collect::<Vec<_>>();
-    let times = lines[0]
-        .split(" ")
-        .filter(|s| s.len() > 0)
-        .skip(1)
-        .map(|s| s.parse().unwrap());
-    let distances = lines[1]
-        .split(" ")
-        .filter(|s| s.len() > 0)
-        .skip(1)
-        .map(|s| s.parse().unwrap());
-
-    (
+fn generate(input: &str) -> Result<(Vec<Race>, Race), ParseError> {
+    let mut lines = lines(6, input);
+    let (times, time) = parse_line(lines.next(), "Time:")?;
+    let (distances, distance) = parse_line(lines.next(), "Distance:")?;
+    if times.len() != distances.len() {
+        return Err(ParseError::input(
+            6,
+            format!("{} times but {} distances", times.len(), distances.len()),
+        ));
+    }
+
+    Ok((
         times
+            .into_iter()
             .zip(distances)
             .map(|(time, distance)| Race { time, distance })
             .collect(),
-        Race {
-            time: lines[0]
-                .split(":")
-                .skip(1)
-                .map(|s| {
-                    s.chars()
-                        .filter(|c| *c >= '0' && *c <= '9')
-                        .collect::<String>()
-                })
-                .collect::<String>()
-                .parse()
-                .unwrap(),
-            distance: lines[1]
-                .split(":")
-                .skip(1)
-                .map(|s| {
-                    s.chars()
-                        .filter(|c| *c >= '0' && *c <= '9')
-                        .collect::<String>()
-                })
-                .collect::<String>()
-                .parse()
-                .unwrap(),
-        },
-    )
+        Race { time, distance },
+    ))
 }
 
 // The idea here is that the distance traveled for a given total race time and
@@ -67,30 +67,293 @@ fn generate(input: &str) -> (Vec<Race>, Race) {
 //
 // x^2 - xb + c < 0
 //
-// Note that the only values of x that satisfy this inequality lie between the
-// roots of this quadratic equation, so all we need to do is compute these two
-// roots and subtract the smaller from the larger (and add one).
+// Note that the only values of x that satisfy this inequality lie strictly
+// between the roots of this quadratic equation, (b - sqrt(b^2 - 4c)) / 2 and
+// (b + sqrt(b^2 - 4c)) / 2. We find the smallest integer above the first one
+// with an integer square root, which can only be off by one, so we nudge it
+// until it's exact. The distances are symmetric around b / 2, so the largest
+// hold time is b minus the smallest. Everything is done in i128, so b^2 can't
+// overflow.
+//
+// Returns the smallest and largest winning hold times. If there aren't any, the
+// largest is one less than the smallest.
 pub fn roots(time: i64, record: i64) -> (i64, i64) {
-    let s = ((time * time - 4 * record) as f64).sqrt();
-    (
-        ((time as f64 - s) / 2.0).ceil() as i64,
-        ((time as f64 + s) / 2.0).floor() as i64,
-    )
+    let (t, c) = (time as i128, record as i128);
+    let wins = |x: i128| x * (t - x) > c;
+    let mid = t / 2;
+    if t < 0 || !wins(mid) {
+        return (0, -1);
+    }
+    // `wins(mid)` means the discriminant is positive.
+    let s = (t * t - 4 * c).isqrt();
+    let mut min = ((t - s).div_euclid(2)).clamp(0, mid);
+    while !wins(min) {
+        min += 1;
+    }
+    while min > 0 && wins(min - 1) {
+        min -= 1;
+    }
+    (min as i64, (t - min) as i64)
+}
+
+/// How fast the boat goes depending on how long the button was held.
+///
+/// The default `roots` relies on the distance being unimodal: for a given race, it mustn't
+/// decrease as the hold time goes up to `best_hold`, and mustn't increase after that. A speed that
+/// never decreases isn't enough on its own. For example, with a speed of 1 for holds below 5 and
+/// 10 after that, a race lasting 10 goes 10, 9, 8, 7 and 6 millimeters for holds 0 to 4, then
+/// jumps to 50.
+pub trait SpeedModel {
+    /// The speed, in millimeters per millisecond, after holding the button for `hold`
+    /// milliseconds. This mustn't decrease as `hold` increases.
+    fn speed(&self, hold: i64) -> i128;
+
+    /// A hold time that goes as far as possible in a race lasting `time`. The distance mustn't
+    /// decrease as the hold time goes up to this, and mustn't increase after it.
+    fn best_hold(&self, time: i64) -> i64;
+
+    /// The distance traveled, saturating at `i128::MAX`. That's still further than any record,
+    /// so it doesn't change which hold times win.
+    fn distance(&self, time: i64, hold: i64) -> i128 {
+        self.speed(hold).saturating_mul((time - hold) as i128)
+    }
+
+    /// Returns the smallest and largest hold times that beat `record` in a race lasting `time`.
+    /// If there aren't any, the largest is one less than the smallest.
+    ///
+    /// By default, this binary searches on either side of the best hold time, which only works if
+    /// the distance is unimodal as described on the trait.
+    fn roots(&self, time: i64, record: i64) -> (i64, i64) {
+        let best = self.best_hold(time);
+        let wins = |hold: i64| self.distance(time, hold) > record as i128;
+        if time < 0 || !wins(best) {
+            return (0, -1);
+        }
+        // The first hold time in `lo..=hi` for which `pred` is true, given that it's true for
+        // `hi` and stays true once it is.
+        let first = |mut lo: i64, mut hi: i64, pred: &dyn Fn(i64) -> bool| {
+            while lo < hi {
+                let mid = lo + (hi - lo) / 2;
+                if pred(mid) {
+                    hi = mid;
+                } else {
+                    lo = mid + 1;
+                }
+            }
+            lo
+        };
+        let min = first(0, best, &wins);
+        // The first losing hold time after `best`, if any, is one past the largest winner.
+        let max = if wins(time) {
+            time
+        } else {
+            first(best, time, &|hold| !wins(hold)) - 1
+        };
+        (min, max)
+    }
+
+    /// The number of hold times that beat `record` in a race lasting `time`.
+    fn ways_to_win(&self, time: i64, record: i64) -> i64 {
+        let (min, max) = self.roots(time, record);
+        max - min + 1
+    }
+}
+
+/// The speed goes up by `acceleration` for every millisecond the button is held. The puzzle's
+/// boats have an acceleration of 1.
+#[derive(Copy, Clone, Debug)]
+pub struct Linear {
+    /// Must be positive.
+    pub acceleration: i64,
+}
+
+impl SpeedModel for Linear {
+    fn speed(&self, hold: i64) -> i128 {
+        self.acceleration as i128 * hold as i128
+    }
+
+    fn best_hold(&self, time: i64) -> i64 {
+        time / 2
+    }
+
+    /// Solves the quadratic exactly. `a * x * (time - x) > record` holds exactly when
+    /// `x * (time - x) > record / a`, rounding down, as the left hand side is an integer.
+    fn roots(&self, time: i64, record: i64) -> (i64, i64) {
+        roots(time, record.div_euclid(self.acceleration))
+    }
+}
+
+/// The speed is `acceleration` times the square of the hold time. The distance grows with the cube
+/// of the race time, so it saturates once the race lasts more than about 2^42 milliseconds.
+#[derive(Copy, Clone, Debug)]
+pub struct Quadratic {
+    /// Must be positive.
+    pub acceleration: i64,
 }
 
+impl SpeedModel for Quadratic {
+    fn speed(&self, hold: i64) -> i128 {
+        (self.acceleration as i128 * hold as i128).saturating_mul(hold as i128)
+    }
+
+    /// `x^2 * (time - x)` peaks at `x = 2 * time / 3`, so the best whole number of milliseconds
+    /// is on one side of that or the other.
+    fn best_hold(&self, time: i64) -> i64 {
+        let x = (2 * time as i128 / 3) as i64;
+        [x, x + 1]
+            .into_iter()
+            .filter(|x| *x <= time)
+            .max_by_key(|x| self.distance(time, *x))
+            .unwrap_or(x)
+    }
+}
+
+/// Like `Linear`, but the speed can't go over `max_speed`.
+#[derive(Copy, Clone, Debug)]
+pub struct Capped {
+    /// Must be positive.
+    pub acceleration: i64,
+    pub max_speed: i64,
+}
+
+impl SpeedModel for Capped {
+    fn speed(&self, hold: i64) -> i128 {
+        (self.acceleration as i128 * hold as i128).min(self.max_speed as i128)
+    }
+
+    /// Before the speed is capped, the distance behaves like `Linear` and peaks halfway through
+    /// the race. After that, holding any longer just wastes time. So the best hold time is
+    /// halfway through the race, or either side of the point where the speed is capped if that
+    /// comes first.
+    fn best_hold(&self, time: i64) -> i64 {
+        let cap = self.max_speed.div_euclid(self.acceleration);
+        [time / 2, cap, cap + 1]
+            .into_iter()
+            .map(|x| x.clamp(0, time.max(0)))
+            .max_by_key(|x| (self.distance(time, *x), -x))
+            .unwrap()
+    }
+}
+
+/// The boats in the puzzle.
+const BOAT: Linear = Linear { acceleration: 1 };
+
 #[aoc(day6, part1)]
 fn solve_part1((input, _): &(Vec<Race>, Race)) -> i64 {
     input
         .iter()
-        .map(|race| {
-            let (min, max) = roots(race.time, race.distance);
-            max - min + 1
-        })
+        .map(|race| BOAT.ways_to_win(race.time, race.distance))
         .product()
 }
 
 #[aoc(day6, part2)]
 fn solve_part2((_, race): &(Vec<Race>, Race)) -> i64 {
-    let (min, max) = roots(race.time, race.distance);
-    max - min + 1
+    BOAT.ways_to_win(race.time, race.distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The winning hold times, found by trying every one.
+    fn brute_force(time: i64, record: i64) -> Vec<i64> {
+        (0..=time).filter(|x| x * (time - x) > record).collect()
+    }
+
+    #[test]
+    fn roots_match_brute_force() {
+        for time in 0..=200 {
+            for record in -5..=time * time / 4 + 1 {
+                let wins = brute_force(time, record);
+                let (min, max) = roots(time, record);
+                let expected = match (wins.first(), wins.last()) {
+                    (Some(&first), Some(&last)) => (first, last),
+                    _ => (0, -1),
+                };
+                assert_eq!((min, max), expected, "time {time}, record {record}");
+                assert_eq!(max - min + 1, wins.len() as i64);
+            }
+        }
+    }
+
+    #[test]
+    fn roots_of_long_races() {
+        for time in [
+            3_000_000_001,
+            1 << 42,
+            4_000_000_000_007,
+            i64::MAX / 2,
+            i64::MAX,
+        ] {
+            let t = time as i128;
+            for record in [-1, 0, t, t * t / 5, t * t / 4 - 1, t * t / 4] {
+                let record = record.min(i64::MAX as i128) as i64;
+                let wins = |x: i64| x as i128 * (t - x as i128) > record as i128;
+                let (min, max) = roots(time, record);
+                if min > max {
+                    assert_eq!((min, max), (0, -1), "time {time}, record {record}");
+                    assert!(!wins(time / 2), "time {time}, record {record}");
+                    continue;
+                }
+                assert!(wins(min) && wins(max), "time {time}, record {record}");
+                assert!(min == 0 || !wins(min - 1), "time {time}, record {record}");
+                assert!(
+                    max == time || !wins(max + 1),
+                    "time {time}, record {record}"
+                );
+            }
+        }
+    }
+
+    /// Checks a model's `roots` and `ways_to_win` against trying every hold time.
+    fn check_model(model: &(impl SpeedModel + std::fmt::Debug), max_time: i64) {
+        for time in 0..=max_time {
+            let best = (0..=time).map(|x| model.distance(time, x)).max().unwrap();
+            for record in (-2..=best as i64 + 1).step_by(1 + best as usize / 300) {
+                let wins: Vec<_> = (0..=time)
+                    .filter(|x| model.distance(time, *x) > record as i128)
+                    .collect();
+                let expected = match (wins.first(), wins.last()) {
+                    (Some(&first), Some(&last)) => (first, last),
+                    _ => (0, -1),
+                };
+                let context = format!("{model:?}, time {time}, record {record}");
+                assert_eq!(model.roots(time, record), expected, "{context}");
+                assert_eq!(
+                    model.ways_to_win(time, record),
+                    wins.len() as i64,
+                    "{context}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn models_match_brute_force() {
+        for acceleration in 1..=3 {
+            check_model(&Linear { acceleration }, 60);
+            check_model(&Quadratic { acceleration }, 60);
+            for max_speed in [1, 2, 5, 17, 40, 1000] {
+                check_model(
+                    &Capped {
+                        acceleration,
+                        max_speed,
+                    },
+                    60,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn quadratic_saturates() {
+        let model = Quadratic { acceleration: 3 };
+        let time = 1 << 50;
+        assert_eq!(model.distance(time, time / 2), i128::MAX);
+        let (min, max) = model.roots(time, i64::MAX);
+        // Only the very shortest and longest holds lose, which needs the exact distance.
+        let exact = |x: i64| 3 * (x as i128).pow(2) * (time - x) as i128;
+        assert!(exact(min) > i64::MAX as i128 && exact(min - 1) <= i64::MAX as i128);
+        assert_eq!(max, time - 1);
+    }
 }
@@ -1,3 +1,4 @@
+use crate::{lines, ParseError};
 use aoc_runner_derive::{aoc, aoc_generator};
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -80,7 +81,7 @@ impl Hand {
             .iter()
             .filter(|c| **c != 11)
             .for_each(|c| *cardmap.entry(c).or_insert(0) += 1);
-        let jokers = self.cards.iter().filter(|c| **c == 11 as u8).count();
+        let jokers = self.cards.iter().filter(|c| **c == 11).count();
         if jokers == 5 {
             return Score::FiveOfAKind;
         }
@@ -117,33 +118,34 @@ impl Hand {
 }
 
 #[aoc_generator(day7)]
-fn generate(input: &str) -> Vec<Hand> {
-    input
-        .lines()
+fn generate(input: &str) -> Result<Vec<Hand>, ParseError> {
+    lines(7, input)
         .map(|line| {
-            let v = line.split(" ").collect::<Vec<_>>();
-            let cs = v[0].chars().collect::<Vec<_>>();
+            let (hand, bid) = line.split_once(" ")?;
+            if hand.chars().count() != 5 {
+                return Err(line.error(hand, "expected 5 cards"));
+            }
             let mut cards = [0; 5];
-            for i in 0..5 {
-                cards[i] = match cs[i] {
-                    '2'..='9' => 2 + cs[i] as u8 - '2' as u8,
+            for (i, c) in hand.chars().enumerate() {
+                cards[i] = match c {
+                    '2'..='9' => 2 + c as u8 - b'2',
                     'T' => 10,
                     'J' => 11,
                     'Q' => 12,
                     'K' => 13,
                     'A' => 14,
-                    _ => panic!("invalid char: {:?}", cs[i]),
+                    _ => return Err(line.invalid_char(i, c)),
                 };
             }
-            let bid: u64 = v[1].parse().unwrap();
-            Hand { cards, bid }
+            let bid: u64 = line.parse(bid)?;
+            Ok(Hand { cards, bid })
         })
         .collect()
 }
 
 #[aoc(day7, part1)]
-fn solve_part1(input: &Vec<Hand>) -> u64 {
-    let mut ranked_hands = input.clone();
+fn solve_part1(input: &[Hand]) -> u64 {
+    let mut ranked_hands = input.to_vec();
     ranked_hands.sort_by(|a, b| {
         if a.score() == b.score() {
             a.cards.cmp(&b.cards)
@@ -159,12 +161,12 @@ fn solve_part1(input: &Vec<Hand>) -> u64 {
 }
 
 #[aoc(day7, part2)]
-fn solve_part2(input: &Vec<Hand>) -> u64 {
-    let mut ranked_hands = input.clone();
+fn solve_part2(input: &[Hand]) -> u64 {
+    let mut ranked_hands = input.to_vec();
     ranked_hands.sort_by(|a, b| {
         if a.score_part2() == b.score_part2() {
-            let mut ac = a.cards.clone();
-            let mut bc = b.cards.clone();
+            let mut ac = a.cards;
+            let mut bc = b.cards;
             for i in 0..5 {
                 if ac[i] == 11 {
                     ac[i] = 0;
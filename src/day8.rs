@@ -1,4 +1,4 @@
-use crate::gcd;
+use crate::{gcd, lines, ParseError};
 use aoc_runner_derive::{aoc, aoc_generator};
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
@@ -20,33 +20,38 @@ struct Input {
 }
 
 #[aoc_generator(day8)]
-fn generate(input: &str) -> Input {
+fn generate(input: &str) -> Result<Input, ParseError> {
     let node_regex =
         regex::Regex::new(r"^([A-Z0-9]{3}) = \(([A-Z0-9]{3}), ([A-Z0-9]{3})\)$").unwrap();
-    let mut it = input.lines();
-    let directions = it
+    let mut it = lines(8, input);
+    let header = it
         .next()
-        .unwrap()
+        .ok_or_else(|| ParseError::input(8, "empty input"))?;
+    let directions = header
+        .text
         .chars()
-        .map(|c| match c {
-            'L' => Dir::Left,
-            'R' => Dir::Right,
-            _ => panic!("invalid direction"),
+        .enumerate()
+        .map(|(i, c)| match c {
+            'L' => Ok(Dir::Left),
+            'R' => Ok(Dir::Right),
+            _ => Err(header.invalid_char(i, c)),
         })
-        .collect();
+        .collect::<Result<_, _>>()?;
     let map = it
         .skip(1)
         .map(|line| {
-            let caps = node_regex.captures(line).unwrap();
-            Node {
+            let caps = node_regex
+                .captures(line.text)
+                .ok_or_else(|| line.error(line.text, "expected \"AAA = (BBB, CCC)\""))?;
+            let node = Node {
                 id: caps[1].into(),
                 left: caps[2].into(),
                 right: caps[3].into(),
-            }
+            };
+            Ok((node.id.clone(), node))
         })
-        .map(|node| (node.id.clone(), node))
-        .collect();
-    Input { map, directions }
+        .collect::<Result<_, _>>()?;
+    Ok(Input { map, directions })
 }
 
 #[aoc(day8, part1)]
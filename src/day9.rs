@@ -1,12 +1,13 @@
+use crate::{lines, ParseError};
 use aoc_runner_derive::{aoc, aoc_generator};
 
 #[aoc_generator(day9)]
-fn generate(input: &str) -> Vec<Vec<i64>> {
-    input
-        .lines()
+fn generate(input: &str) -> Result<Vec<Vec<i64>>, ParseError> {
+    lines(9, input)
         .map(|line| {
-            line.split_whitespace()
-                .map(|s| s.parse().unwrap())
+            line.text
+                .split_whitespace()
+                .map(|s| line.parse(s))
                 .collect()
         })
         .collect()
@@ -35,12 +36,12 @@ fn extrapolate(sequence: Vec<i64>) -> i64 {
 }
 
 #[aoc(day9, part1)]
-fn solve_part1(input: &Vec<Vec<i64>>) -> i64 {
+fn solve_part1(input: &[Vec<i64>]) -> i64 {
     input.iter().map(|seq| extrapolate(seq.clone())).sum()
 }
 
 #[aoc(day9, part2)]
-fn solve_part2(input: &Vec<Vec<i64>>) -> i64 {
+fn solve_part2(input: &[Vec<i64>]) -> i64 {
     input
         .iter()
         .map(|seq| extrapolate(seq.iter().cloned().rev().collect()))
@@ -14,6 +14,10 @@ pub mod day19;
 pub mod day2;
 pub mod day20;
 pub mod day21;
+pub mod day22;
+pub mod day23;
+pub mod day24;
+pub mod day25;
 pub mod day3;
 pub mod day4;
 pub mod day5;
@@ -21,6 +25,139 @@ pub mod day6;
 pub mod day7;
 pub mod day8;
 pub mod day9;
+pub mod ranges;
+pub mod search;
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::{BuildHasher, Hash};
+use std::str::FromStr;
+
+/// An error encountered while parsing a puzzle input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub day: u32,
+    /// 1-based line and column of the offending text, if the error refers to a specific location
+    /// in the input.
+    pub position: Option<(usize, usize)>,
+    /// The offending text.
+    pub text: String,
+    pub message: String,
+}
+
+impl ParseError {
+    /// Returns an error that applies to the input as a whole (e.g., a missing start tile).
+    pub fn input<S: Into<String>>(day: u32, message: S) -> Self {
+        ParseError {
+            day,
+            position: None,
+            text: String::new(),
+            message: message.into(),
+        }
+    }
+
+    /// Returns an error pointing at `text`, which starts at the 0-based line `y` and char index
+    /// `x`.
+    pub fn at<S: Into<String>>(day: u32, (y, x): (usize, usize), text: &str, message: S) -> Self {
+        ParseError {
+            day,
+            position: Some((y + 1, x + 1)),
+            text: text.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "day {}", self.day)?;
+        if let Some((line, column)) = self.position {
+            write!(f, ", line {line}, column {column}")?;
+        }
+        write!(f, ": {}", self.message)?;
+        if !self.text.is_empty() {
+            write!(f, ": {:?}", self.text)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A single line of puzzle input, which knows where it came from so that it can produce
+/// `ParseError`s.
+#[derive(Copy, Clone, Debug)]
+pub struct Line<'a> {
+    pub day: u32,
+    /// 0-based line index.
+    pub index: usize,
+    pub text: &'a str,
+}
+
+impl<'a> Line<'a> {
+    /// Returns the 1-based column at which `span` starts. `span` should be a substring of
+    /// `self.text`.
+    fn column_of(&self, span: &str) -> usize {
+        let start = self.text.as_ptr() as usize;
+        let p = span.as_ptr() as usize;
+        let offset = if p >= start && p <= start + self.text.len() {
+            p - start
+        } else {
+            self.text.find(span).unwrap_or(0)
+        };
+        self.text[..offset].chars().count() + 1
+    }
+
+    /// Returns an error pointing at `span`, which should be a substring of this line.
+    pub fn error<S: Into<String>>(&self, span: &str, message: S) -> ParseError {
+        ParseError {
+            day: self.day,
+            position: Some((self.index + 1, self.column_of(span))),
+            text: span.to_string(),
+            message: message.into(),
+        }
+    }
+
+    /// Returns an error pointing just past the end of this line, for when something is missing.
+    pub fn error_at_end<S: Into<String>>(&self, message: S) -> ParseError {
+        self.error(&self.text[self.text.len()..], message)
+    }
+
+    /// Returns an error pointing at `text`, which starts at 0-based char index `x`.
+    pub fn error_at<S: Into<String>>(&self, x: usize, text: &str, message: S) -> ParseError {
+        ParseError::at(self.day, (self.index, x), text, message)
+    }
+
+    /// Returns an error for an unexpected char at 0-based char index `x`.
+    pub fn invalid_char(&self, x: usize, c: char) -> ParseError {
+        self.error_at(x, &c.to_string(), "invalid char")
+    }
+
+    /// Parses `span` (a substring of this line) as a `T`.
+    pub fn parse<T>(&self, span: &str) -> Result<T, ParseError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        span.parse().map_err(|e| self.error(span, format!("{e}")))
+    }
+
+    /// Splits this line on `pat`, returning the text on either side.
+    pub fn split_once(&self, pat: &str) -> Result<(&'a str, &'a str), ParseError> {
+        self.text
+            .split_once(pat)
+            .ok_or_else(|| self.error_at_end(format!("expected {pat:?}")))
+    }
+}
+
+/// Iterates over the lines of `input`, tagged with their positions.
+pub fn lines(day: u32, input: &str) -> impl Iterator<Item = Line<'_>> {
+    input
+        .lines()
+        .enumerate()
+        .map(move |(index, text)| Line { day, index, text })
+}
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
 pub struct Bounds {
@@ -40,6 +177,14 @@ pub trait Plane {
             height: self.height(),
         }
     }
+    /// Returns true iff `(y, x)` lies on this plane. Unbounded planes should override this.
+    fn contains(&self, (y, x): (i64, i64)) -> bool {
+        let (min_x, min_y) = self.origin();
+        x >= min_x
+            && y >= min_y
+            && x < min_x + self.width() as i64
+            && y < min_y + self.height() as i64
+    }
 }
 
 impl<T> Plane for array2d::Array2D<T> {
@@ -66,6 +211,287 @@ impl Plane for Bounds {
     }
 }
 
+/// Returns the cells of `p` that surround a horizontal span of `len` cells starting at `(y, x)`,
+/// including the diagonals. With a `len` of 1, these are the 8 neighbours of `(y, x)`.
+pub fn surrounding<P: Plane>(
+    (y, x): (i64, i64),
+    len: usize,
+    p: &P,
+) -> impl Iterator<Item = (i64, i64)> + '_ {
+    let len = len as i64;
+    (y - 1..=y + 1)
+        .flat_map(move |ny| (x - 1..=x + len).map(move |nx| (ny, nx)))
+        .filter(move |&(ny, nx)| (ny != y || nx < x || nx >= x + len) && p.contains((ny, nx)))
+}
+
+/// A rectangular grid of cells, indexed by `(y, x)` relative to its origin.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Grid<T> {
+    cells: Vec<T>,
+    bounds: Bounds,
+}
+
+impl<T> Grid<T> {
+    /// Builds a grid from a list of rows, which must all be the same length.
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Self {
+        let height = rows.len();
+        let width = rows.first().map_or(0, |row| row.len());
+        assert!(rows.iter().all(|row| row.len() == width), "ragged rows");
+        Grid {
+            cells: rows.into_iter().flatten().collect(),
+            bounds: Bounds {
+                origin: (0, 0),
+                width,
+                height,
+            },
+        }
+    }
+
+    pub fn filled_with(v: T, width: usize, height: usize) -> Self
+    where
+        T: Clone,
+    {
+        Grid {
+            cells: vec![v; width * height],
+            bounds: Bounds {
+                origin: (0, 0),
+                width,
+                height,
+            },
+        }
+    }
+
+    /// Moves the top left corner of the grid to `origin`, which is given as `(x, y)` like
+    /// `Plane::origin`.
+    pub fn with_origin(mut self, origin: (i64, i64)) -> Self {
+        self.bounds.origin = origin;
+        self
+    }
+
+    /// Parses a grid with one row per line, converting each char with `f`. Chars for which `f`
+    /// returns `None` are reported as invalid.
+    pub fn parse<F>(day: u32, input: &str, f: F) -> Result<Self, ParseError>
+    where
+        F: FnMut(char) -> Option<T>,
+    {
+        Self::parse_lines(day, lines(day, input), f)
+    }
+
+    /// Like `parse`, but for a subset of the input's lines.
+    pub fn parse_lines<'a, I, F>(day: u32, lines: I, mut f: F) -> Result<Self, ParseError>
+    where
+        I: IntoIterator<Item = Line<'a>>,
+        F: FnMut(char) -> Option<T>,
+    {
+        let mut rows: Vec<Vec<T>> = vec![];
+        for line in lines {
+            let row = line
+                .text
+                .chars()
+                .enumerate()
+                .map(|(x, c)| f(c).ok_or_else(|| line.invalid_char(x, c)))
+                .collect::<Result<Vec<_>, _>>()?;
+            if let Some(first) = rows.first() {
+                if row.len() != first.len() {
+                    return Err(line.error(line.text, format!("expected {} columns", first.len())));
+                }
+            }
+            rows.push(row);
+        }
+        if rows.is_empty() || rows[0].is_empty() {
+            return Err(ParseError::input(day, "empty grid"));
+        }
+        Ok(Self::from_rows(rows))
+    }
+
+    fn offset(&self, pos: (i64, i64)) -> Option<usize> {
+        if self.contains(pos) {
+            let (min_x, min_y) = self.bounds.origin;
+            Some((pos.0 - min_y) as usize * self.bounds.width + (pos.1 - min_x) as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, pos: (i64, i64)) -> Option<&T> {
+        self.offset(pos).map(|i| &self.cells[i])
+    }
+
+    pub fn get_mut(&mut self, pos: (i64, i64)) -> Option<&mut T> {
+        self.offset(pos).map(|i| &mut self.cells[i])
+    }
+
+    /// Returns the row at `y`.
+    pub fn row(&self, y: i64) -> &[T] {
+        let start = (y - self.bounds.origin.1) as usize * self.bounds.width;
+        &self.cells[start..start + self.bounds.width]
+    }
+
+    /// Iterates over the column at `x`, from top to bottom.
+    pub fn column(&self, x: i64) -> impl Iterator<Item = &T> {
+        let start = (x - self.bounds.origin.0) as usize;
+        self.cells[start..].iter().step_by(self.bounds.width)
+    }
+
+    pub fn rows(&self) -> std::slice::Chunks<'_, T> {
+        self.cells.chunks(self.bounds.width.max(1))
+    }
+
+    /// Iterates over all positions in the grid, in row-major order.
+    pub fn positions(&self) -> impl Iterator<Item = (i64, i64)> {
+        let (min_x, min_y) = self.bounds.origin;
+        let (width, height) = (self.bounds.width as i64, self.bounds.height as i64);
+        (min_y..min_y + height).flat_map(move |y| (min_x..min_x + width).map(move |x| (y, x)))
+    }
+
+    /// Iterates over all cells in the grid along with their positions, in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = ((i64, i64), &T)> {
+        self.positions().zip(self.cells.iter())
+    }
+
+    /// Returns the position of the first cell (in row-major order) satisfying `pred`.
+    pub fn find<F: FnMut(&T) -> bool>(&self, mut pred: F) -> Option<(i64, i64)> {
+        self.iter().find(|(_, t)| pred(t)).map(|(pos, _)| pos)
+    }
+
+    /// Returns the position of the only cell satisfying `pred`, or an error mentioning `what` if
+    /// there isn't exactly one.
+    pub fn find_unique<F>(
+        &self,
+        day: u32,
+        what: &str,
+        mut pred: F,
+    ) -> Result<(i64, i64), ParseError>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let mut found = self.iter().filter(|(_, t)| pred(t)).map(|(pos, _)| pos);
+        let pos = found
+            .next()
+            .ok_or_else(|| ParseError::input(day, format!("no {what}")))?;
+        match found.next() {
+            None => Ok(pos),
+            Some((y, x)) => {
+                let (min_x, min_y) = self.bounds.origin;
+                Err(ParseError::at(
+                    day,
+                    ((y - min_y) as usize, (x - min_x) as usize),
+                    "",
+                    format!("more than one {what}"),
+                ))
+            }
+        }
+    }
+
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> Grid<U> {
+        Grid {
+            cells: self.cells.iter().map(f).collect(),
+            bounds: self.bounds,
+        }
+    }
+}
+
+impl<T> Plane for Grid<T> {
+    fn origin(&self) -> (i64, i64) {
+        self.bounds.origin
+    }
+    fn width(&self) -> usize {
+        self.bounds.width
+    }
+    fn height(&self) -> usize {
+        self.bounds.height
+    }
+}
+
+impl<T> std::ops::Index<(i64, i64)> for Grid<T> {
+    type Output = T;
+    fn index(&self, pos: (i64, i64)) -> &Self::Output {
+        match self.offset(pos) {
+            Some(i) => &self.cells[i],
+            None => panic!("{pos:?} out of bounds for {:?}", self.bounds),
+        }
+    }
+}
+
+impl<T> std::ops::IndexMut<(i64, i64)> for Grid<T> {
+    fn index_mut(&mut self, pos: (i64, i64)) -> &mut Self::Output {
+        match self.offset(pos) {
+            Some(i) => &mut self.cells[i],
+            None => panic!("{pos:?} out of bounds for {:?}", self.bounds),
+        }
+    }
+}
+
+impl<T: Display> Display for Grid<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.rows() {
+            for cell in row {
+                write!(f, "{cell}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// An infinite plane made by tiling copies of a finite plane in every direction. Positions are
+/// global, and lookups wrap around into the underlying plane.
+#[derive(Copy, Clone, Debug)]
+pub struct TiledPlane<'a, P> {
+    inner: &'a P,
+}
+
+impl<'a, P: Plane> TiledPlane<'a, P> {
+    pub fn new(inner: &'a P) -> Self {
+        TiledPlane { inner }
+    }
+
+    /// Maps a global position to the corresponding position in the underlying plane.
+    pub fn wrap(&self, (y, x): (i64, i64)) -> (i64, i64) {
+        let (min_x, min_y) = self.inner.origin();
+        (
+            min_y + (y - min_y).rem_euclid(self.inner.height() as i64),
+            min_x + (x - min_x).rem_euclid(self.inner.width() as i64),
+        )
+    }
+
+    /// Returns the `(y, x)` index of the copy of the underlying plane that contains a global
+    /// position. The original copy is `(0, 0)`.
+    pub fn tile(&self, (y, x): (i64, i64)) -> (i64, i64) {
+        let (min_x, min_y) = self.inner.origin();
+        (
+            (y - min_y).div_euclid(self.inner.height() as i64),
+            (x - min_x).div_euclid(self.inner.width() as i64),
+        )
+    }
+}
+
+/// `width` and `height` are those of a single tile.
+impl<P: Plane> Plane for TiledPlane<'_, P> {
+    fn origin(&self) -> (i64, i64) {
+        self.inner.origin()
+    }
+    fn width(&self) -> usize {
+        self.inner.width()
+    }
+    fn height(&self) -> usize {
+        self.inner.height()
+    }
+    fn contains(&self, _: (i64, i64)) -> bool {
+        true
+    }
+}
+
+impl<P> std::ops::Index<(i64, i64)> for TiledPlane<'_, P>
+where
+    P: Plane + std::ops::Index<(i64, i64)>,
+{
+    type Output = P::Output;
+    fn index(&self, pos: (i64, i64)) -> &Self::Output {
+        &self.inner[self.wrap(pos)]
+    }
+}
+
 #[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
 pub enum Dir {
     N,
@@ -111,39 +537,14 @@ impl Dir {
         }
     }
 
+    /// Returns the position one step in this direction from `(y, x)`, if `p` contains it.
     pub fn neighbor<P: Plane>(self, (y, x): (i64, i64), p: &P) -> Option<(i64, i64)> {
-        let (min_x, min_y) = p.origin();
-        let max_x = min_x + p.width() as i64;
-        let max_y = min_y + p.height() as i64;
-        match self {
-            Dir::N => {
-                if y == min_y {
-                    None
-                } else {
-                    Some((y - 1, x))
-                }
-            }
-            Dir::S => {
-                if y >= max_y - 1 {
-                    None
-                } else {
-                    Some((y + 1, x))
-                }
-            }
-            Dir::W => {
-                if x == min_x {
-                    None
-                } else {
-                    Some((y, x - 1))
-                }
-            }
-            Dir::E => {
-                if x >= max_x - 1 {
-                    None
-                } else {
-                    Some((y, x + 1))
-                }
-            }
+        let (dy, dx) = self.delta();
+        let next = (y + dy, x + dx);
+        if p.contains(next) {
+            Some(next)
+        } else {
+            None
         }
     }
 
@@ -182,11 +583,10 @@ impl Dir {
             }
         }
     }
-    pub fn neighbors<P: Plane>((y, x): (i64, i64), p: &P) -> impl Iterator<Item = (i64, i64)> {
-        let bounds = p.extract();
+    pub fn neighbors<P: Plane>((y, x): (i64, i64), p: &P) -> impl Iterator<Item = (i64, i64)> + '_ {
         [Dir::N, Dir::S, Dir::E, Dir::W]
             .into_iter()
-            .filter_map(move |dir| dir.neighbor((y, x), &bounds))
+            .filter_map(move |dir| dir.neighbor((y, x), p))
     }
 }
 
@@ -198,4 +598,185 @@ fn gcd<T: std::ops::Rem<Output = T> + Eq + Default + Copy>(a: T, b: T) -> T {
     }
 }
 
+/// Returns `(g, x, y)` such that `g` is the greatest common divisor of `a` and `b`, and
+/// `a * x + b * y == g`.
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a, 1, 0)
+    } else {
+        let (g, x, y) = extended_gcd(b, a % b);
+        (g, y, x - (a / b) * y)
+    }
+}
+
+/// Finds the smallest non-negative `x` such that `x ≡ a (mod m)` for every `(a, m)`, and returns
+/// it along with the least common multiple of the moduli, which is the period of the solutions.
+/// The moduli don't need to be coprime. Returns None if there is no solution, or if the least
+/// common multiple doesn't fit in an i128.
+fn crt(congruences: &[(i128, i128)]) -> Option<(i128, i128)> {
+    let (mut x, mut period) = (0, 1);
+    for &(a, m) in congruences {
+        let (g, p, _) = extended_gcd(period, m);
+        if (a - x) % g != 0 {
+            return None;
+        }
+        // `p * period ≡ g (mod m)`, so stepping `x` forward by `(a - x) / g * p` periods makes it
+        // congruent to `a` without disturbing the congruences we already have.
+        let steps = ((a - x) / g % (m / g) * p).rem_euclid(m / g);
+        let lcm = (period / g).checked_mul(m)?;
+        x = (x + period * steps).rem_euclid(lcm);
+        period = lcm;
+    }
+    Some((x, period))
+}
+
+/// The states of a deterministic simulation, from the start up to the point where it begins
+/// repeating itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct StateCycle<S> {
+    /// Every state from the start up to, but not including, the first repeat.
+    history: Vec<S>,
+    /// The number of steps before the repeating part starts.
+    offset: usize,
+    period: usize,
+}
+
+impl<S> StateCycle<S> {
+    /// Returns the index into `history` of the state after `n` steps.
+    fn index(&self, n: u64) -> usize {
+        if n < self.history.len() as u64 {
+            n as usize
+        } else {
+            self.offset + ((n - self.offset as u64) % self.period as u64) as usize
+        }
+    }
+}
+
+/// Steps a simulation from `start` until it either gets back to a state it has been in before,
+/// in which case it returns the cycle, or it has taken `limit` steps, in which case it returns
+/// all the states so far.
+fn simulate<S, F>(start: S, limit: u64, mut step: F) -> Result<StateCycle<S>, Vec<S>>
+where
+    S: Eq + Hash,
+    F: FnMut(&S) -> S,
+{
+    // States can be big, so rather than keeping a copy of each one as a key, we look them up by
+    // hash and compare them with the history.
+    let hasher = RandomState::new();
+    let mut seen = HashMap::<u64, Vec<usize>>::new();
+    seen.entry(hasher.hash_one(&start)).or_default().push(0);
+    let mut history = vec![start];
+    while (history.len() as u64) <= limit {
+        let next = step(history.last().unwrap());
+        let same_hash = seen.entry(hasher.hash_one(&next)).or_default();
+        if let Some(&offset) = same_hash.iter().find(|i| history[**i] == next) {
+            let period = history.len() - offset;
+            return Ok(StateCycle {
+                history,
+                offset,
+                period,
+            });
+        }
+        same_hash.push(history.len());
+        history.push(next);
+    }
+    Err(history)
+}
+
+/// Steps a simulation from `start` until it gets back to a state it has been in before.
+fn find_cycle<S, F>(start: S, step: F) -> StateCycle<S>
+where
+    S: Eq + Hash,
+    F: FnMut(&S) -> S,
+{
+    match simulate(start, u64::MAX, step) {
+        Ok(cycle) => cycle,
+        Err(_) => unreachable!("took more than u64::MAX steps"),
+    }
+}
+
+/// Returns the state of a simulation after `n` steps from `start`. Once the simulation starts
+/// repeating itself, the answer is looked up in the states seen so far rather than simulated.
+fn state_after<S, F>(start: S, n: u64, step: F) -> S
+where
+    S: Eq + Hash,
+    F: FnMut(&S) -> S,
+{
+    match simulate(start, n, step) {
+        Ok(mut cycle) => {
+            let i = cycle.index(n);
+            cycle.history.swap_remove(i)
+        }
+        Err(mut history) => history.pop().unwrap(),
+    }
+}
+
 aoc_lib! { year = 2023 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn surrounding_cells() {
+        let grid = Grid::filled_with(0, 4, 3);
+        let around = |pos: (i64, i64), len: i64, p: &dyn Fn((i64, i64)) -> bool| {
+            let mut cells: Vec<_> = (-1..=1)
+                .flat_map(|dy| (-1..=len).map(move |dx| (dy, dx)))
+                .map(|(dy, dx)| (pos.0 + dy, pos.1 + dx))
+                .filter(|&(y, x)| (y != pos.0 || x < pos.1 || x >= pos.1 + len) && p((y, x)))
+                .collect();
+            cells.sort();
+            cells
+        };
+        for pos in [(0, 0), (1, 1), (2, 3), (1, 2)] {
+            for len in 1..=3 {
+                let mut clipped: Vec<_> = surrounding(pos, len as usize, &grid).collect();
+                clipped.sort();
+                assert_eq!(clipped, around(pos, len, &|c| grid.contains(c)));
+                let tiled = TiledPlane::new(&grid);
+                let mut unclipped: Vec<_> = surrounding(pos, len as usize, &tiled).collect();
+                unclipped.sort();
+                assert_eq!(unclipped, around(pos, len, &|_| true));
+                assert_eq!(unclipped.len() as i64, 2 * len + 6);
+            }
+        }
+    }
+
+    #[test]
+    fn crt_coprime() {
+        assert_eq!(crt(&[(2, 3), (3, 5), (2, 7)]), Some((23, 105)));
+        assert_eq!(crt(&[]), Some((0, 1)));
+    }
+
+    #[test]
+    fn crt_non_coprime() {
+        assert_eq!(crt(&[(2, 4), (4, 6)]), Some((10, 12)));
+        assert_eq!(crt(&[(3, 6), (3, 6), (1, 2)]), Some((3, 6)));
+    }
+
+    #[test]
+    fn crt_inconsistent() {
+        assert_eq!(crt(&[(1, 4), (2, 6)]), None);
+        assert_eq!(crt(&[(0, 2), (1, 2)]), None);
+    }
+
+    #[test]
+    fn crt_matches_brute_force() {
+        for m1 in 1..=8 {
+            for m2 in 1..=8 {
+                for a1 in 0..m1 {
+                    for a2 in 0..m2 {
+                        let lcm = m1 * m2 / gcd(m1, m2);
+                        let expected = (0..lcm).find(|x| x % m1 == a1 && x % m2 == a2);
+                        assert_eq!(
+                            crt(&[(a1, m1), (a2, m2)]),
+                            expected.map(|x| (x, lcm)),
+                            "x ≡ {a1} (mod {m1}), x ≡ {a2} (mod {m2})"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,466 @@
+/// The integers from `start` up to, but not including, `end`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+}
+
+impl Range {
+    pub fn new(start: i64, end: i64) -> Self {
+        Range { start, end }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+
+    pub fn contains(&self, x: i64) -> bool {
+        self.start <= x && x < self.end
+    }
+}
+
+/// A set of integers, stored as a sorted list of disjoint ranges. Ranges that touch are merged,
+/// so two sets are equal exactly when they have the same elements.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RangeSet {
+    ranges: Vec<Range>,
+}
+
+impl RangeSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the union of `ranges`, which can be in any order and may overlap.
+    pub fn from_ranges(ranges: impl IntoIterator<Item = Range>) -> Self {
+        let mut ranges = ranges
+            .into_iter()
+            .filter(|r| !r.is_empty())
+            .collect::<Vec<_>>();
+        ranges.sort();
+        let mut merged: Vec<Range> = vec![];
+        for r in ranges {
+            match merged.last_mut() {
+                Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+                _ => merged.push(r),
+            }
+        }
+        RangeSet { ranges: merged }
+    }
+
+    pub fn ranges(&self) -> &[Range] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// The number of integers in the set.
+    pub fn len(&self) -> u64 {
+        self.ranges.iter().map(|r| r.end.abs_diff(r.start)).sum()
+    }
+
+    pub fn min(&self) -> Option<i64> {
+        self.ranges.first().map(|r| r.start)
+    }
+
+    pub fn contains(&self, x: i64) -> bool {
+        let i = self.ranges.partition_point(|r| r.end <= x);
+        self.ranges.get(i).is_some_and(|r| r.contains(x))
+    }
+
+    pub fn union(&self, other: &RangeSet) -> RangeSet {
+        RangeSet::from_ranges(self.ranges.iter().chain(&other.ranges).copied())
+    }
+
+    pub fn intersection(&self, other: &RangeSet) -> RangeSet {
+        let mut result = vec![];
+        let (mut i, mut j) = (0, 0);
+        while let (Some(a), Some(b)) = (self.ranges.get(i), other.ranges.get(j)) {
+            let r = Range::new(a.start.max(b.start), a.end.min(b.end));
+            if !r.is_empty() {
+                result.push(r);
+            }
+            // Whichever range ends first can't overlap anything else in the other set.
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        RangeSet { ranges: result }
+    }
+
+    /// Returns the integers in `self` that aren't in `other`.
+    pub fn difference(&self, other: &RangeSet) -> RangeSet {
+        let mut result = vec![];
+        let mut j = 0;
+        for a in &self.ranges {
+            let mut start = a.start;
+            // Skip the ranges of `other` that end before this one starts. They can't overlap any
+            // later ranges of `self` either.
+            while other.ranges.get(j).is_some_and(|b| b.end <= start) {
+                j += 1;
+            }
+            let mut k = j;
+            while let Some(b) = other.ranges.get(k).filter(|b| b.start < a.end) {
+                if start < b.start {
+                    result.push(Range::new(start, b.start));
+                }
+                start = start.max(b.end);
+                k += 1;
+            }
+            if start < a.end {
+                result.push(Range::new(start, a.end));
+            }
+        }
+        RangeSet { ranges: result }
+    }
+
+    /// Adds `diff` to every element.
+    pub fn shift(&self, diff: i64) -> RangeSet {
+        RangeSet {
+            ranges: self
+                .ranges
+                .iter()
+                .map(|r| Range::new(r.start + diff, r.end + diff))
+                .collect(),
+        }
+    }
+}
+
+impl From<Range> for RangeSet {
+    fn from(range: Range) -> Self {
+        RangeSet::from_ranges([range])
+    }
+}
+
+/// Adds `diff` to the integers in `start..end`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Offset {
+    pub start: i64,
+    pub end: i64,
+    pub diff: i64,
+}
+
+impl Offset {
+    pub fn range(&self) -> Range {
+        Range::new(self.start, self.end)
+    }
+}
+
+/// A function on the integers that adds a different amount to each of a number of disjoint
+/// ranges, and leaves everything else as it is.
+///
+/// The pieces are kept sorted, with no pieces that add zero and no touching pieces that add the
+/// same amount, so two maps are equal exactly when they are the same function.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PiecewiseOffsetMap {
+    pieces: Vec<Offset>,
+}
+
+impl PiecewiseOffsetMap {
+    /// The map that leaves everything as it is.
+    pub fn identity() -> Self {
+        Self::default()
+    }
+
+    /// Builds a map from `offsets`, which may overlap, in which case the first one that contains
+    /// a number applies to it.
+    pub fn from_offsets(offsets: impl IntoIterator<Item = Offset>) -> Self {
+        let mut covered = RangeSet::new();
+        let mut pieces = vec![];
+        for o in offsets {
+            let here = RangeSet::from(o.range());
+            for r in here.difference(&covered).ranges() {
+                pieces.push(Offset {
+                    start: r.start,
+                    end: r.end,
+                    diff: o.diff,
+                });
+            }
+            covered = covered.union(&here);
+        }
+        Self::from_disjoint(pieces)
+    }
+
+    /// Builds a map from pieces that don't overlap, putting them into the canonical form.
+    fn from_disjoint(mut pieces: Vec<Offset>) -> Self {
+        pieces.retain(|p| p.diff != 0 && p.start < p.end);
+        pieces.sort();
+        let mut merged: Vec<Offset> = vec![];
+        for p in pieces {
+            match merged.last_mut() {
+                Some(last) if last.end == p.start && last.diff == p.diff => last.end = p.end,
+                _ => merged.push(p),
+            }
+        }
+        PiecewiseOffsetMap { pieces: merged }
+    }
+
+    pub fn pieces(&self) -> &[Offset] {
+        &self.pieces
+    }
+
+    /// The numbers that the map changes.
+    pub fn domain(&self) -> RangeSet {
+        RangeSet::from_ranges(self.pieces.iter().map(Offset::range))
+    }
+
+    pub fn apply(&self, x: i64) -> i64 {
+        let i = self.pieces.partition_point(|p| p.end <= x);
+        match self.pieces.get(i) {
+            Some(p) if p.start <= x => x + p.diff,
+            _ => x,
+        }
+    }
+
+    /// Returns everything that the numbers in `set` map to.
+    pub fn image(&self, set: &RangeSet) -> RangeSet {
+        let mut result = set.difference(&self.domain());
+        for p in &self.pieces {
+            let moved = set.intersection(&p.range().into()).shift(p.diff);
+            result = result.union(&moved);
+        }
+        result
+    }
+
+    /// Returns every number that maps to something in `set`.
+    pub fn preimage(&self, set: &RangeSet) -> RangeSet {
+        let mut result = set.difference(&self.domain());
+        for p in &self.pieces {
+            let from = set.shift(-p.diff).intersection(&p.range().into());
+            result = result.union(&from);
+        }
+        result
+    }
+
+    /// Returns the map that applies `self` and then `next`.
+    pub fn then(&self, next: &PiecewiseOffsetMap) -> PiecewiseOffsetMap {
+        let domain = self.domain();
+        let mut pieces = vec![];
+        let mut add = |set: &RangeSet, diff: i64| {
+            pieces.extend(set.ranges().iter().map(|r| Offset {
+                start: r.start,
+                end: r.end,
+                diff,
+            }))
+        };
+        for p in &self.pieces {
+            // Split each of our pieces up by which of `next`'s pieces it lands in.
+            let mut rest = RangeSet::from(p.range());
+            for q in &next.pieces {
+                let both = rest.intersection(&RangeSet::from(q.range()).shift(-p.diff));
+                add(&both, p.diff + q.diff);
+                rest = rest.difference(&both);
+            }
+            add(&rest, p.diff);
+        }
+        // Everywhere else, only `next` does anything.
+        for q in &next.pieces {
+            add(&RangeSet::from(q.range()).difference(&domain), q.diff);
+        }
+        Self::from_disjoint(pieces)
+    }
+
+    /// Returns the map that undoes this one, if there is one. That's the case when no two numbers
+    /// map to the same thing, i.e. the pieces move their ranges onto exactly the numbers that the
+    /// map changes, without any overlaps.
+    pub fn inverse(&self) -> Option<PiecewiseOffsetMap> {
+        let images = self
+            .pieces
+            .iter()
+            .map(|p| Range::new(p.start + p.diff, p.end + p.diff))
+            .collect::<Vec<_>>();
+        let image = RangeSet::from_ranges(images.iter().copied());
+        let total = images.iter().map(|r| r.end.abs_diff(r.start)).sum::<u64>();
+        if image.len() != total || image != self.domain() {
+            return None;
+        }
+        Some(Self::from_disjoint(
+            images
+                .iter()
+                .zip(&self.pieces)
+                .map(|(r, p)| Offset {
+                    start: r.start,
+                    end: r.end,
+                    diff: -p.diff,
+                })
+                .collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every number that any of the tests can move anything to or from.
+    const WINDOW: std::ops::Range<i64> = -30..70;
+
+    /// A small xorshift generator, so the tests are repeatable.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: i64) -> i64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as i64
+        }
+
+        fn range(&mut self) -> Range {
+            let start = self.below(30);
+            Range::new(start, start + self.below(9))
+        }
+
+        fn set(&mut self) -> RangeSet {
+            let n = self.below(4);
+            RangeSet::from_ranges((0..n).map(|_| self.range()))
+        }
+
+        fn map(&mut self) -> PiecewiseOffsetMap {
+            let n = self.below(5);
+            PiecewiseOffsetMap::from_offsets((0..n).map(|_| {
+                let r = self.range();
+                Offset {
+                    start: r.start,
+                    end: r.end,
+                    diff: self.below(13) - 6,
+                }
+            }))
+        }
+    }
+
+    fn elements(set: &RangeSet) -> Vec<i64> {
+        WINDOW.filter(|x| set.contains(*x)).collect()
+    }
+
+    #[test]
+    fn set_operations() {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        for _ in 0..2000 {
+            let (a, b) = (rng.set(), rng.set());
+            let both = a.intersection(&b);
+            let only_a = a.difference(&b);
+            for x in WINDOW {
+                assert_eq!(
+                    both.contains(x),
+                    a.contains(x) && b.contains(x),
+                    "{a:?} & {b:?}"
+                );
+                assert_eq!(
+                    only_a.contains(x),
+                    a.contains(x) && !b.contains(x),
+                    "{a:?} - {b:?}"
+                );
+            }
+            // The results are canonical, so they compare equal to the sets built from scratch.
+            let rebuild = |set: &RangeSet| {
+                RangeSet::from_ranges(elements(set).into_iter().map(|x| Range::new(x, x + 1)))
+            };
+            assert_eq!(both, rebuild(&both));
+            assert_eq!(only_a, rebuild(&only_a));
+            assert_eq!(both.len() + only_a.len(), a.len());
+        }
+    }
+
+    #[test]
+    fn image_and_preimage() {
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+        for _ in 0..2000 {
+            let (map, set) = (rng.map(), rng.set());
+            let image = map.image(&set);
+            let preimage = map.preimage(&set);
+            for x in WINDOW {
+                let maps_to_x = WINDOW.clone().any(|y| set.contains(y) && map.apply(y) == x);
+                assert_eq!(image.contains(x), maps_to_x, "{map:?} of {set:?}");
+                assert_eq!(
+                    preimage.contains(x),
+                    set.contains(map.apply(x)),
+                    "{map:?} of {set:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn composition() {
+        let mut rng = Rng(0xd1b54a32d192ed03);
+        for _ in 0..2000 {
+            let (first, second) = (rng.map(), rng.map());
+            let both = first.then(&second);
+            for x in WINDOW {
+                assert_eq!(
+                    both.apply(x),
+                    second.apply(first.apply(x)),
+                    "{first:?} then {second:?}"
+                );
+            }
+            let pointwise = PiecewiseOffsetMap::from_offsets(WINDOW.map(|x| Offset {
+                start: x,
+                end: x + 1,
+                diff: both.apply(x) - x,
+            }));
+            assert_eq!(both, pointwise);
+        }
+    }
+
+    #[test]
+    fn inverse() {
+        let mut rng = Rng(0x853c49e6748fea9b);
+        let (mut injective, mut not_injective) = (0, 0);
+        for _ in 0..5000 {
+            let map = rng.map();
+            let outputs: Vec<_> = WINDOW.map(|x| map.apply(x)).collect();
+            let distinct = outputs
+                .iter()
+                .collect::<std::collections::HashSet<_>>()
+                .len();
+            match map.inverse() {
+                Some(inverse) => {
+                    injective += 1;
+                    assert_eq!(distinct, outputs.len(), "{map:?}");
+                    for x in WINDOW {
+                        assert_eq!(inverse.apply(map.apply(x)), x, "{map:?}");
+                    }
+                    assert_eq!(map.then(&inverse), PiecewiseOffsetMap::identity());
+                    assert_eq!(inverse.then(&map), PiecewiseOffsetMap::identity());
+                }
+                None => {
+                    not_injective += 1;
+                    assert!(distinct < outputs.len(), "{map:?}");
+                }
+            }
+        }
+        assert!(
+            injective > 100 && not_injective > 100,
+            "{injective} {not_injective}"
+        );
+    }
+
+    #[test]
+    fn swap_is_its_own_inverse() {
+        let swap = PiecewiseOffsetMap::from_offsets([
+            Offset {
+                start: 0,
+                end: 5,
+                diff: 5,
+            },
+            Offset {
+                start: 5,
+                end: 10,
+                diff: -5,
+            },
+        ]);
+        assert_eq!(swap.inverse(), Some(swap.clone()));
+        let shift = PiecewiseOffsetMap::from_offsets([Offset {
+            start: 0,
+            end: 5,
+            diff: 5,
+        }]);
+        assert_eq!(shift.apply(0), shift.apply(5));
+        assert_eq!(shift.inverse(), None);
+    }
+}
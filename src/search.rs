@@ -0,0 +1,132 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+
+/// Finds the cheapest way from one of `starts` to a state for which `is_goal` returns true, and
+/// returns the states along the way, ending with the goal, along with the cost of reaching it.
+///
+/// `successors` yields the states reachable from a state in one move, along with the cost of the
+/// move, which must not be negative. `heuristic` estimates the remaining cost from a state to the
+/// nearest goal; the result is only guaranteed to be optimal if it never overestimates.
+pub fn astar<S, I, FS, FH, FG>(
+    starts: impl IntoIterator<Item = S>,
+    mut successors: FS,
+    mut heuristic: FH,
+    mut is_goal: FG,
+) -> Option<(Vec<S>, i64)>
+where
+    S: Clone + Eq + Hash,
+    I: IntoIterator<Item = (S, i64)>,
+    FS: FnMut(&S) -> I,
+    FH: FnMut(&S) -> i64,
+    FG: FnMut(&S) -> bool,
+{
+    // The states themselves don't need to be ordered, so the heap holds indices into `states`.
+    // Alongside each state we keep the index of the one we reached it from.
+    let mut states: Vec<(S, Option<usize>)> = vec![];
+    let mut best = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    for start in starts {
+        if best.insert(start.clone(), 0).is_none() {
+            queue.push(Reverse((heuristic(&start), 0, states.len())));
+            states.push((start, None));
+        }
+    }
+    while let Some(Reverse((_, cost, i))) = queue.pop() {
+        let state = states[i].0.clone();
+        if best[&state] < cost {
+            // We already found a cheaper way here.
+            continue;
+        }
+        if is_goal(&state) {
+            let mut path = vec![];
+            let mut at = Some(i);
+            while let Some(j) = at {
+                path.push(states[j].0.clone());
+                at = states[j].1;
+            }
+            path.reverse();
+            return Some((path, cost));
+        }
+        for (next, step) in successors(&state) {
+            let next_cost = cost + step;
+            if best.get(&next).is_none_or(|c| next_cost < *c) {
+                best.insert(next.clone(), next_cost);
+                queue.push(Reverse((
+                    next_cost + heuristic(&next),
+                    next_cost,
+                    states.len(),
+                )));
+                states.push((next, Some(i)));
+            }
+        }
+    }
+    None
+}
+
+/// Splits a graph into its strongly connected components, the largest groups of nodes that can
+/// all reach each other. The nodes are numbered from 0 up to `nodes`, and `successors` yields the
+/// nodes that a node has edges to.
+///
+/// The components are returned in reverse topological order, so each one comes after all of the
+/// components it has edges to.
+pub fn strongly_connected_components<I, FS>(nodes: usize, mut successors: FS) -> Vec<Vec<usize>>
+where
+    I: IntoIterator<Item = usize>,
+    FS: FnMut(usize) -> I,
+{
+    // This is Tarjan's algorithm, with the recursion turned into a loop over `calls` so that long
+    // chains of nodes can't overflow the stack.
+    const UNSEEN: usize = usize::MAX;
+    let mut index = vec![UNSEEN; nodes];
+    let mut low = vec![0; nodes];
+    let mut on_stack = vec![false; nodes];
+    let mut stack = vec![];
+    let mut components = vec![];
+    let mut next_index = 0;
+    for root in 0..nodes {
+        if index[root] != UNSEEN {
+            continue;
+        }
+        let mut calls: Vec<(usize, I::IntoIter)> = vec![];
+        let mut enter = Some(root);
+        loop {
+            if let Some(node) = enter.take() {
+                index[node] = next_index;
+                low[node] = next_index;
+                next_index += 1;
+                stack.push(node);
+                on_stack[node] = true;
+                calls.push((node, successors(node).into_iter()));
+            }
+            let Some((node, rest)) = calls.last_mut() else {
+                break;
+            };
+            let node = *node;
+            if let Some(next) = rest.next() {
+                if index[next] == UNSEEN {
+                    enter = Some(next);
+                } else if on_stack[next] {
+                    low[node] = low[node].min(index[next]);
+                }
+                continue;
+            }
+            calls.pop();
+            if let Some((parent, _)) = calls.last() {
+                low[*parent] = low[*parent].min(low[node]);
+            }
+            if low[node] == index[node] {
+                let mut component = vec![];
+                while let Some(m) = stack.pop() {
+                    on_stack[m] = false;
+                    component.push(m);
+                    if m == node {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+    components
+}